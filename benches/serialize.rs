@@ -0,0 +1,82 @@
+// Baseline numbers for packet building and sending, run with `cargo bench`
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use firmware_protocol::deku::prelude::*;
+use firmware_protocol::{Packet, SbPacket, SensorDataType, SlimeQuaternion};
+use std::hint::black_box;
+use tokio::net::UdpSocket;
+use tokio::runtime::Runtime;
+use tracker_emulation_rs::EmulatedTracker;
+
+const QUAT: SlimeQuaternion = SlimeQuaternion {
+    i: 0.0,
+    j: 0.383,
+    k: 0.0,
+    w: 0.924,
+};
+
+fn rotation_packet(seq: u64) -> Packet<SbPacket> {
+    Packet::new(
+        seq,
+        SbPacket::RotationData {
+            sensor_id: 0,
+            data_type: SensorDataType::Normal,
+            quat: QUAT,
+            calibration_info: 0,
+        },
+    )
+}
+
+fn serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("rotation_data", |b| {
+        let mut seq = 0;
+        b.iter(|| {
+            seq += 1;
+            black_box(rotation_packet(black_box(seq)).to_bytes().unwrap())
+        })
+    });
+    group.finish();
+}
+
+// Connects a tracker to a loopback socket that just answers the first packet so init returns
+async fn connected_tracker() -> (EmulatedTracker, UdpSocket) {
+    let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let mut tracker = EmulatedTracker::builder()
+        .server_ip("127.0.0.1")
+        .server_discovery_port(server.local_addr().unwrap().port())
+        .build()
+        .unwrap();
+
+    let answer = async {
+        let mut buf = [0u8; 1024];
+        let (_, addr) = server.recv_from(&mut buf).await.unwrap();
+        // Heartbeat: packet type 1, sequence number 0
+        let mut heartbeat = 1u32.to_be_bytes().to_vec();
+        heartbeat.extend_from_slice(&0u64.to_be_bytes());
+        server.send_to(&heartbeat, addr).await.unwrap();
+    };
+    let (result, _) = tokio::join!(tracker.init(), answer);
+    result.unwrap();
+    (tracker, server)
+}
+
+fn send(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let (tracker, _server) = runtime.block_on(connected_tracker());
+
+    let mut group = c.benchmark_group("send");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("send_rotation_loopback", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(tracker.send_rotation(0, SensorDataType::Normal, QUAT, 0))
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, serialize, send);
+criterion_main!(benches);
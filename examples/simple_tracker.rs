@@ -0,0 +1,53 @@
+// Emulates a tracker with two sensors swinging back and forth until Ctrl-C.
+// Run with a SlimeVR server on the same network: cargo run --example simple_tracker
+
+use firmware_protocol::{BoardType, ImuType, McuType, SensorDataType, SensorStatus};
+use std::time::Duration;
+use tokio::time::{interval, Instant};
+use tracker_emulation_rs::motion::{sine_rotation, Axis, MotionGenerator};
+use tracker_emulation_rs::EmulatedTracker;
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let mut tracker = EmulatedTracker::builder()
+        .board_type(BoardType::Custom)
+        .mcu_type(McuType::Esp32)
+        .primary_imu(ImuType::Bno085)
+        .build()?;
+
+    println!("Looking for a server...");
+    tracker.init().await?;
+    println!("Connected");
+
+    tracker
+        .add_sensors([
+            (ImuType::Bno085, SensorStatus::Ok),
+            (ImuType::Bno085, SensorStatus::Ok),
+        ])
+        .await?;
+
+    let swings = [
+        sine_rotation(Axis::X, 0.8, 0.5),
+        sine_rotation(Axis::Z, 0.4, 0.25),
+    ];
+    let mut ticker = interval(Duration::from_millis(10));
+    let start = Instant::now();
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                let t = start.elapsed().as_secs_f32();
+                for (sensor_id, swing) in swings.iter().enumerate() {
+                    tracker
+                        .send_rotation(sensor_id as u8, SensorDataType::Normal, swing.rotation(t), 0)
+                        .await?;
+                }
+            }
+            _ = &mut ctrl_c => break,
+        }
+    }
+
+    println!("Shutting down");
+    tracker.deinit().await
+}
@@ -0,0 +1,489 @@
+// Blocking version of the tracker for code that doesn't run an async runtime.
+// Mirrors the async API one-to-one, each call just blocks on the async version using a runtime owned by the
+// tracker (multi-threaded so the heartbeat keeps running between calls). The one exception is builder(): the builder
+// makes an async tracker, so pass it to from_builder instead.
+
+use crate::noise::NoiseConfig;
+use crate::{
+    AccelFrame, EmulatedTrackerBuilder, HealthReport, Recorder, SendTarget, ServerInfo,
+    TrackerConfig, TrackerEvent, TrackerState, TrackerStats, TrackerStatus, Vec3,
+};
+use firmware_protocol::{
+    ActionType, BoardType, CbPacket, ImuType, McuType, SbPacket, SensorDataType, SensorStatus,
+    SlimeQuaternion,
+};
+use futures::StreamExt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::sync::broadcast;
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle;
+
+pub struct EmulatedTracker {
+    runtime: Runtime,
+    tracker: crate::EmulatedTracker,
+}
+
+impl EmulatedTracker {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        mac_address: [u8; 6],
+        firmware_version: String,
+        board_type: Option<BoardType>,
+        mcu_type: Option<McuType>,
+        server_ip: Option<String>,
+        server_discovery_port: Option<u16>,
+        server_timeout_ms: Option<u64>,
+        debug: Option<bool>,
+    ) -> Result<Self, String> {
+        let runtime = build_runtime()?;
+        let tracker = runtime.block_on(crate::EmulatedTracker::new(
+            mac_address,
+            firmware_version,
+            board_type,
+            mcu_type,
+            server_ip,
+            server_discovery_port,
+            server_timeout_ms,
+            debug,
+        ))?;
+        Ok(Self { runtime, tracker })
+    }
+
+    pub fn from_builder(builder: EmulatedTrackerBuilder) -> Result<Self, String> {
+        Self::from_async(builder.build()?)
+    }
+
+    pub fn from_async(tracker: crate::EmulatedTracker) -> Result<Self, String> {
+        Ok(Self {
+            runtime: build_runtime()?,
+            tracker,
+        })
+    }
+
+    // The runtime comes along because an initialized tracker's background tasks (heartbeat, receiving, simulations)
+    // run on it, dropping it would kill them. It can go once the tracker is deinitialized.
+    pub fn into_async(self) -> (crate::EmulatedTracker, Runtime) {
+        (self.tracker, self.runtime)
+    }
+
+    pub fn get_state(&self) -> TrackerState {
+        self.runtime.block_on(self.tracker.get_state())
+    }
+
+    pub fn time_since_heartbeat(&self) -> Option<Duration> {
+        self.runtime.block_on(self.tracker.time_since_heartbeat())
+    }
+
+    pub fn uptime(&self) -> Option<Duration> {
+        self.runtime.block_on(self.tracker.uptime())
+    }
+
+    pub fn connected_duration(&self) -> Option<Duration> {
+        self.runtime.block_on(self.tracker.connected_duration())
+    }
+
+    pub fn config_snapshot(&self) -> TrackerConfig {
+        self.runtime.block_on(self.tracker.config_snapshot())
+    }
+
+    pub fn health_check(&self) -> HealthReport {
+        self.runtime.block_on(self.tracker.health_check())
+    }
+
+    pub fn send_target(&self) -> SendTarget {
+        self.runtime.block_on(self.tracker.send_target())
+    }
+
+    pub fn packet_number(&self) -> u64 {
+        self.tracker.packet_number()
+    }
+
+    #[cfg(feature = "testing")]
+    pub fn set_packet_number(&self, packet_number: u64) {
+        self.tracker.set_packet_number(packet_number)
+    }
+
+    pub fn stats(&self) -> TrackerStats {
+        self.runtime.block_on(self.tracker.stats())
+    }
+
+    pub fn last_sent(&self) -> Vec<Vec<u8>> {
+        self.runtime.block_on(self.tracker.last_sent())
+    }
+
+    pub fn last_received(&self) -> Vec<Vec<u8>> {
+        self.runtime.block_on(self.tracker.last_received())
+    }
+
+    /*
+     * Server init functions
+     */
+
+    pub fn init(&mut self) -> Result<(), String> {
+        self.runtime.block_on(self.tracker.init())
+    }
+
+    pub fn run_until(&mut self, shutdown: impl Future) -> Result<(), String> {
+        self.runtime.block_on(self.tracker.run_until(shutdown))
+    }
+
+    pub fn set_server(&self, addr: SocketAddr) -> Result<(), String> {
+        self.runtime.block_on(self.tracker.set_server(addr))
+    }
+
+    pub fn deinit(&mut self) -> Result<(), String> {
+        self.runtime.block_on(self.tracker.deinit())
+    }
+
+    pub fn try_deinit(&mut self) -> Result<(), String> {
+        self.runtime.block_on(self.tracker.try_deinit())
+    }
+
+    pub fn force_deinit(&mut self) {
+        self.runtime.block_on(self.tracker.force_deinit())
+    }
+
+    pub fn flush(&self) {
+        self.runtime.block_on(self.tracker.flush())
+    }
+
+    pub fn set_debug(&self, enabled: bool) {
+        self.tracker.set_debug(enabled)
+    }
+
+    pub fn subscribe_status(&self) -> Receiver<TrackerStatus> {
+        self.tracker.subscribe_status()
+    }
+
+    pub fn subscribe_state(&self) -> Receiver<TrackerState> {
+        self.tracker.subscribe_state()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.runtime.block_on(self.tracker.is_connected())
+    }
+
+    // Blocks until the tracker is connected
+    pub fn connected(&self) {
+        self.runtime.block_on(self.tracker.connected())
+    }
+
+    // Blocks until the tracker is no longer connected
+    pub fn disconnected(&self) {
+        self.runtime.block_on(self.tracker.disconnected())
+    }
+
+    pub fn subscribe_events(&self) -> broadcast::Receiver<TrackerEvent> {
+        self.tracker.subscribe_events()
+    }
+
+    // Blocks for each packet, ends like the async stream does
+    pub fn incoming(&self) -> impl Iterator<Item = CbPacket> + '_ {
+        let mut stream = Box::pin(self.tracker.incoming());
+        std::iter::from_fn(move || self.runtime.block_on(stream.next()))
+    }
+
+    pub fn server_info(&self) -> Option<ServerInfo> {
+        self.runtime.block_on(self.tracker.server_info())
+    }
+
+    pub fn uses_modern_packets(&self) -> bool {
+        self.runtime.block_on(self.tracker.uses_modern_packets())
+    }
+
+    pub fn attach_recorder(&mut self, recorder: Recorder) {
+        self.tracker.attach_recorder(recorder)
+    }
+
+    pub fn detach_recorder(&mut self) -> Option<Recorder> {
+        self.tracker.detach_recorder()
+    }
+
+    /*
+     * Tracker functions
+     */
+
+    pub fn add_sensor(
+        &mut self,
+        sensor_type: ImuType,
+        sensor_status: SensorStatus,
+    ) -> Result<(), String> {
+        self.runtime
+            .block_on(self.tracker.add_sensor(sensor_type, sensor_status))
+    }
+
+    pub fn add_sensor_with_id(
+        &mut self,
+        sensor_id: u8,
+        sensor_type: ImuType,
+        sensor_status: SensorStatus,
+    ) -> Result<(), String> {
+        self.runtime.block_on(self.tracker.add_sensor_with_id(
+            sensor_id,
+            sensor_type,
+            sensor_status,
+        ))
+    }
+
+    pub fn add_sensors(
+        &mut self,
+        specs: impl IntoIterator<Item = (ImuType, SensorStatus)>,
+    ) -> Result<Vec<u8>, String> {
+        self.runtime.block_on(self.tracker.add_sensors(specs))
+    }
+
+    pub fn connected_sensor_ids(&self) -> Vec<u8> {
+        self.tracker.connected_sensor_ids()
+    }
+
+    pub fn is_sensor_acknowledged(&self, sensor_id: u8) -> bool {
+        self.runtime
+            .block_on(self.tracker.is_sensor_acknowledged(sensor_id))
+    }
+
+    pub fn update_sensor(&mut self, sensor_id: u8, new_type: ImuType) -> Result<(), String> {
+        self.runtime
+            .block_on(self.tracker.update_sensor(sensor_id, new_type))
+    }
+
+    pub fn resend_handshake(&self) -> Result<(), String> {
+        self.runtime.block_on(self.tracker.resend_handshake())
+    }
+
+    pub fn announce_sensors(&self) -> Result<(), String> {
+        self.runtime.block_on(self.tracker.announce_sensors())
+    }
+
+    pub fn pause(&self) {
+        self.tracker.pause()
+    }
+
+    pub fn resume(&self) {
+        self.tracker.resume()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.tracker.is_paused()
+    }
+
+    pub fn last_rotation(&self, sensor_id: u8) -> Option<SlimeQuaternion> {
+        self.tracker.last_rotation(sensor_id)
+    }
+
+    pub fn send_rotation(
+        &self,
+        sensor_id: u8,
+        data_type: SensorDataType,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+    ) -> Result<u64, String> {
+        self.runtime.block_on(self.tracker.send_rotation(
+            sensor_id,
+            data_type,
+            rotation_data,
+            accuracy,
+        ))
+    }
+
+    pub fn send_rotation_all(
+        &self,
+        data_type: SensorDataType,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+    ) -> Result<Vec<u64>, String> {
+        self.runtime.block_on(
+            self.tracker
+                .send_rotation_all(data_type, rotation_data, accuracy),
+        )
+    }
+
+    pub fn send_rotation_noisy(
+        &self,
+        sensor_id: u8,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+        noise: &NoiseConfig,
+    ) -> Result<u64, String> {
+        self.runtime.block_on(self.tracker.send_rotation_noisy(
+            sensor_id,
+            rotation_data,
+            accuracy,
+            noise,
+        ))
+    }
+
+    pub fn send_correction(
+        &self,
+        sensor_id: u8,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+    ) -> Result<u64, String> {
+        self.runtime.block_on(
+            self.tracker
+                .send_correction(sensor_id, rotation_data, accuracy),
+        )
+    }
+
+    pub fn send_acceleration(
+        &self,
+        sensor_id: u8,
+        acceleration: impl Into<Vec3>,
+    ) -> Result<u64, String> {
+        self.runtime
+            .block_on(self.tracker.send_acceleration(sensor_id, acceleration))
+    }
+
+    pub fn send_acceleration_in(
+        &self,
+        sensor_id: u8,
+        acceleration: impl Into<Vec3>,
+        frame: AccelFrame,
+    ) -> Result<u64, String> {
+        self.runtime.block_on(
+            self.tracker
+                .send_acceleration_in(sensor_id, acceleration, frame),
+        )
+    }
+
+    pub fn send_imu_frame(
+        &self,
+        sensor_id: u8,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+        acceleration: impl Into<Vec3>,
+    ) -> Result<(u64, u64), String> {
+        self.runtime.block_on(self.tracker.send_imu_frame(
+            sensor_id,
+            rotation_data,
+            accuracy,
+            acceleration,
+        ))
+    }
+
+    pub fn send_rotation_at(
+        &self,
+        sensor_id: u8,
+        data_type: SensorDataType,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+        at: Instant,
+    ) -> Result<u64, String> {
+        self.runtime.block_on(self.tracker.send_rotation_at(
+            sensor_id,
+            data_type,
+            rotation_data,
+            accuracy,
+            at,
+        ))
+    }
+
+    pub fn send_acceleration_at(
+        &self,
+        sensor_id: u8,
+        acceleration: impl Into<Vec3>,
+        at: Instant,
+    ) -> Result<u64, String> {
+        self.runtime.block_on(
+            self.tracker
+                .send_acceleration_at(sensor_id, acceleration, at),
+        )
+    }
+
+    pub fn send_battery_level(&self, percentage: f32, voltage: f32) -> Result<u64, String> {
+        self.runtime
+            .block_on(self.tracker.send_battery_level(percentage, voltage))
+    }
+
+    pub fn send_temperature(&self, sensor_id: u8, temperature: f32) -> Result<u64, String> {
+        self.runtime
+            .block_on(self.tracker.send_temperature(sensor_id, temperature))
+    }
+
+    pub fn send_signal_strength(&self, sensor_id: u8, strength: i8) -> Result<u64, String> {
+        self.runtime
+            .block_on(self.tracker.send_signal_strength(sensor_id, strength))
+    }
+
+    pub fn send_magnetometer_accuracy(&self, sensor_id: u8, accuracy: f32) -> Result<u64, String> {
+        self.runtime
+            .block_on(self.tracker.send_magnetometer_accuracy(sensor_id, accuracy))
+    }
+
+    pub fn send_flex_data(&self, sensor_id: u8, value: f32) -> Result<u64, String> {
+        self.runtime
+            .block_on(self.tracker.send_flex_data(sensor_id, value))
+    }
+
+    pub fn send_position(&self, sensor_id: u8, position: impl Into<Vec3>) -> Result<u64, String> {
+        self.runtime
+            .block_on(self.tracker.send_position(sensor_id, position))
+    }
+
+    pub fn send_user_action(&self, action: ActionType) -> Result<u64, String> {
+        self.runtime.block_on(self.tracker.send_user_action(action))
+    }
+
+    pub fn send_ping(&self) -> Result<u64, String> {
+        self.runtime.block_on(self.tracker.send_ping())
+    }
+
+    pub fn send_heartbeat(&self) -> Result<u64, String> {
+        self.runtime.block_on(self.tracker.send_heartbeat())
+    }
+
+    pub fn send_packet_to(&self, data: SbPacket, addr: SocketAddr) -> Result<u64, String> {
+        self.runtime
+            .block_on(self.tracker.send_packet_to(data, addr))
+    }
+
+    pub fn send_raw(&self, bytes: &[u8]) -> Result<(), String> {
+        self.runtime.block_on(self.tracker.send_raw(bytes))
+    }
+
+    /*
+     * Simulation functions
+     */
+
+    pub fn start_battery_simulation(
+        &self,
+        start_pct: f32,
+        drain_per_min: f32,
+        voltage_curve: impl Fn(f32) -> f32 + Send + 'static,
+    ) -> Result<JoinHandle<()>, String> {
+        let _guard = self.runtime.enter();
+        self.tracker
+            .start_battery_simulation(start_pct, drain_per_min, voltage_curve)
+    }
+
+    pub fn start_mag_accuracy_simulation(
+        &self,
+        sensor_ids: impl IntoIterator<Item = u8>,
+        min: f32,
+        max: f32,
+    ) -> Result<JoinHandle<()>, String> {
+        let _guard = self.runtime.enter();
+        self.tracker
+            .start_mag_accuracy_simulation(sensor_ids, min, max)
+    }
+
+    pub fn start_signal_strength_simulation(
+        &self,
+        min_dbm: i8,
+        max_dbm: i8,
+    ) -> Result<JoinHandle<()>, String> {
+        let _guard = self.runtime.enter();
+        self.tracker
+            .start_signal_strength_simulation(min_dbm, max_dbm)
+    }
+}
+
+fn build_runtime() -> Result<Runtime, String> {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .map_err(|e| format!("Failed to create runtime: {e}"))
+}
@@ -0,0 +1,550 @@
+use crate::clock::{Clock, SystemClock};
+use crate::error::MAX_FIRMWARE_VERSION_LEN;
+use crate::logging::PacketHistory;
+use crate::mac::{mac_from_str, random_mac};
+use crate::names::HardwareId;
+use crate::net::is_broadcast_address;
+use crate::sans_io::{Handshake, TrackerCore};
+use crate::{
+    EmulatedTracker, FirmwareFeatureFlags, JumpAction, PacketNegotiation, ProtocolMode, SendPath,
+    SendTarget, ServerSelection, StatePublisher, TrackerError, TrackerState, TrackerStats,
+    TrackerStatus, DEFAULT_MAX_DATAGRAM_SIZE,
+};
+use firmware_protocol::{BoardType, ImuType, McuType};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use tokio::sync::{Mutex, Notify};
+
+#[derive(Default)]
+pub struct EmulatedTrackerBuilder {
+    pub(crate) mac_address: Option<[u8; 6]>,
+    pub(crate) firmware_version: Option<String>,
+    pub(crate) board_type: Option<BoardType>,
+    pub(crate) mcu_type: Option<McuType>,
+    pub(crate) server_ip: Option<String>,
+    // Set by broadcast_address, so the tracker knows server_ip is a broadcast address and not a server
+    pub(crate) broadcast_address: Option<Ipv4Addr>,
+    pub(crate) server_discovery_port: Option<u16>,
+    pub(crate) server_data_port: Option<u16>,
+    pub(crate) server_selection: Option<ServerSelection>,
+    pub(crate) server_timeout_ms: Option<u64>,
+    pub(crate) timeout_check_interval: Option<Duration>,
+    pub(crate) debug: Option<bool>,
+    pub(crate) recv_buffer_size: Option<usize>,
+    pub(crate) packet_history: Option<usize>,
+    pub(crate) socket_send_buffer: Option<usize>,
+    pub(crate) socket_recv_buffer: Option<usize>,
+    pub(crate) keep_socket_on_deinit: Option<bool>,
+    pub(crate) separate_data_socket: Option<bool>,
+    pub(crate) dry_run: Option<bool>,
+    pub(crate) sensor_info_retry: Option<(Duration, u32)>,
+    pub(crate) data_rate_hz: Option<f32>,
+    pub(crate) handshake_backoff: Option<(Duration, Duration)>,
+    pub(crate) handshake_burst: Option<(u32, Duration)>,
+    pub(crate) reconnect_after_failures: Option<u32>,
+    pub(crate) bundle_packets: Option<bool>,
+    pub(crate) max_datagram_size: Option<usize>,
+    pub(crate) rotation_guard: Option<(f32, JumpAction)>,
+    pub(crate) packet_negotiation: Option<PacketNegotiation>,
+    pub(crate) auto_heartbeat: Option<bool>,
+    pub(crate) clock: Option<Arc<dyn Clock>>,
+    pub(crate) protocol_mode: Option<ProtocolMode>,
+    pub(crate) feature_flags: Option<FirmwareFeatureFlags>,
+    pub(crate) primary_imu: Option<ImuType>,
+    pub(crate) build_number: Option<u32>,
+    pub(crate) imu_info: Option<(u32, u32, u32)>,
+    pub(crate) position_packet_type: Option<u32>,
+    #[cfg(feature = "testing")]
+    pub(crate) simulated_loss: Option<f32>,
+    #[cfg(feature = "testing")]
+    pub(crate) simulated_latency: Option<Duration>,
+    #[cfg(feature = "testing")]
+    pub(crate) freeze_heartbeat_numbering: Option<bool>,
+    // Deferred so the setters can stay chainable, reported by build()
+    pub(crate) error: Option<TrackerError>,
+}
+
+impl EmulatedTrackerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Defaults to a random locally-administered MAC if not set
+    pub fn mac_address(mut self, mac_address: [u8; 6]) -> Self {
+        self.mac_address = Some(mac_address);
+        self
+    }
+
+    pub fn mac_address_str(mut self, mac_address: &str) -> Self {
+        match mac_from_str(mac_address) {
+            Ok(mac_address) => self.mac_address = Some(mac_address),
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    pub fn firmware_version(mut self, firmware_version: impl Into<String>) -> Self {
+        self.firmware_version = Some(firmware_version.into());
+        self
+    }
+
+    pub fn board_type(mut self, board_type: BoardType) -> Self {
+        self.board_type = Some(board_type);
+        self
+    }
+
+    pub fn mcu_type(mut self, mcu_type: McuType) -> Self {
+        self.mcu_type = Some(mcu_type);
+        self
+    }
+
+    // IMU the handshake reports. Without it the handshake falls back to the first sensor's type, but sensors can only
+    // be added once init has sent the first handshake, so that one reports an unknown IMU and only later ones (after
+    // resend_handshake or a reconnect) have the sensor's type. Set this if the server should see it from the start.
+    pub fn primary_imu(mut self, imu_type: ImuType) -> Self {
+        self.primary_imu = Some(imu_type);
+        self
+    }
+
+    // Firmware build number sent in the handshake (defaults to 13)
+    pub fn build_number(mut self, build_number: u32) -> Self {
+        self.build_number = Some(build_number);
+        self
+    }
+
+    // The handshake's imu_info field (defaults to all zeros). Firmware doesn't fill it in either, so this is only
+    // for matching a specific device byte for byte.
+    pub fn imu_info(mut self, imu_info: (u32, u32, u32)) -> Self {
+        self.imu_info = Some(imu_info);
+        self
+    }
+
+    // Packet type send_position uses. The official protocol doesn't have a position packet (yet), so there's no
+    // default and send_position returns an error until this is set to whatever the server being tested expects.
+    pub fn position_packet_type(mut self, packet_type: u32) -> Self {
+        self.position_packet_type = Some(packet_type);
+        self
+    }
+
+    // Discovers the server on this broadcast address instead of 255.255.255.255, for networks that only let subnet
+    // broadcasts through (see subnet_broadcast). Replaces server_ip.
+    pub fn broadcast_address(mut self, addr: Ipv4Addr) -> Self {
+        if is_broadcast_address(addr) {
+            self.server_ip = Some(addr.to_string());
+            self.broadcast_address = Some(addr);
+        } else {
+            self.error = Some(TrackerError::InvalidBroadcastAddress(addr));
+        }
+        self
+    }
+
+    pub fn server_ip(mut self, server_ip: impl Into<String>) -> Self {
+        self.server_ip = Some(server_ip.into());
+        self.broadcast_address = None;
+        self
+    }
+
+    // Sets the server IP and discovery port in one go
+    pub fn server_addr(mut self, addr: impl Into<SocketAddr>) -> Self {
+        let addr = addr.into();
+        self.server_ip = Some(addr.ip().to_string());
+        self.broadcast_address = None;
+        self.server_discovery_port = Some(addr.port());
+        self
+    }
+
+    pub fn server_ipv4(self, ip: [u8; 4], port: u16) -> Self {
+        self.server_addr((Ipv4Addr::from(ip), port))
+    }
+
+    // Port handshakes are sent to (defaults to 6969)
+    pub fn server_discovery_port(mut self, port: u16) -> Self {
+        self.server_discovery_port = Some(port);
+        self
+    }
+
+    // Port data is sent to once connected, defaults to whichever port the server answered from
+    pub fn server_data_port(mut self, port: u16) -> Self {
+        self.server_data_port = Some(port);
+        self
+    }
+
+    // Which server to connect to if several answer, all of them show up in discovered_servers either way
+    pub fn server_selection(mut self, selection: ServerSelection) -> Self {
+        self.server_selection = Some(selection);
+        self
+    }
+
+    pub fn server_timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.server_timeout_ms = Some(timeout_ms);
+        self
+    }
+
+    // How often the heartbeat timeout is checked, so a lost server is noticed at most this long after the timeout
+    // (defaults to a quarter of the timeout)
+    pub fn timeout_check_interval(mut self, interval: Duration) -> Self {
+        self.timeout_check_interval = Some(interval);
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = Some(debug);
+        self
+    }
+
+    // Largest datagram that can be received from the server, bigger ones get truncated (defaults to 1024 bytes)
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    // Keeps copies of the last `depth` datagrams sent and received, for last_sent/last_received. Off (0) by default
+    // since every datagram gets copied.
+    pub fn packet_history(mut self, depth: usize) -> Self {
+        self.packet_history = Some(depth);
+        self
+    }
+
+    // OS buffer sizes for the socket (SO_SNDBUF/SO_RCVBUF), left at the OS default if not set. The defaults can
+    // overflow with lots of sensors at a high rate: 16 sensors sending rotation and acceleration at 100Hz is around
+    // 3200 packets a second, 256 KiB for both is plenty for that. The OS may round or cap the size (on Linux see
+    // net.core.wmem_max/rmem_max).
+    pub fn socket_send_buffer(mut self, size: usize) -> Self {
+        self.socket_send_buffer = Some(size);
+        self
+    }
+
+    pub fn socket_recv_buffer(mut self, size: usize) -> Self {
+        self.socket_recv_buffer = Some(size);
+        self
+    }
+
+    // Keeps the socket bound through deinit, so every init uses the same local port instead of a new one. Handy when
+    // a firewall rule or the server keys on the port. Sends between deinit and init still go out on it. Off by
+    // default.
+    pub fn keep_socket_on_deinit(mut self, enabled: bool) -> Self {
+        self.keep_socket_on_deinit = Some(enabled);
+        self
+    }
+
+    // Sends rotation and acceleration from a second socket bound next to the main one, so a burst of data can't
+    // hold up heartbeats and other control packets queued behind it. Handshake, heartbeat, pings and commands stay on
+    // the main socket, which is also the only one listened on. The data comes from a different local port than the
+    // handshake did, so servers that tell trackers apart by address and port (like the SlimeVR server) may not match
+    // it to this tracker. Off by default.
+    pub fn separate_data_socket(mut self, enabled: bool) -> Self {
+        self.separate_data_socket = Some(enabled);
+        self
+    }
+
+    // Builds and numbers every packet like normal, but nothing is ever sent: no handshakes, heartbeats or data, and
+    // nothing is received either. init doesn't wait for a server, the tracker counts as connected straight away.
+    // Meant for checking packet construction without a network (e.g. in CI), together with packet_history to look
+    // at the bytes with last_sent. The socket still gets bound since that's what makes the tracker initialized.
+    // Off by default.
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = Some(enabled);
+        self
+    }
+
+    // Resends SensorInfo every `retry_interval` until the server acknowledges the sensor, up to `max_retries` times
+    pub fn reliable_sensor_info(mut self, retry_interval: Duration, max_retries: u32) -> Self {
+        self.sensor_info_retry = Some((retry_interval, max_retries));
+        self
+    }
+
+    // Handshakes are retried after `base`, doubling every time nobody answers until they're `max` apart
+    // (defaults to 1s and 10s). Pass the same value twice for a fixed interval. build() fails with
+    // TrackerError::InvalidHandshakeBackoff if `base` is zero.
+    pub fn handshake_backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.handshake_backoff = Some((base, max));
+        self
+    }
+
+    // Sends the first `count` handshakes of every discovery `spacing` apart before falling back to handshake_backoff,
+    // so a dropped first broadcast (busy WiFi) doesn't cost a whole retry interval. Off by default.
+    pub fn handshake_burst(mut self, count: u32, spacing: Duration) -> Self {
+        self.handshake_burst = Some((count, spacing));
+        self
+    }
+
+    // Goes back to looking for the server after this many sends in a row fail (e.g. the network interface went
+    // down), instead of staying connected and failing forever. Off by default.
+    pub fn reconnect_after_failures(mut self, failures: u32) -> Self {
+        self.reconnect_after_failures = Some(failures.max(1));
+        self
+    }
+
+    // Combines packets sent together (like add_sensors) into a single bundle datagram, only turn this on for servers
+    // that understand bundles
+    pub fn bundle_packets(mut self, enabled: bool) -> Self {
+        self.bundle_packets = Some(enabled);
+        self
+    }
+
+    // Biggest datagram a bundle can turn into, anything more gets split over several bundles. Defaults to
+    // DEFAULT_MAX_DATAGRAM_SIZE, which fits a 1500 byte Ethernet MTU without the IP packet getting fragmented.
+    pub fn max_datagram_size(mut self, size: usize) -> Self {
+        self.max_datagram_size = Some(size);
+        self
+    }
+
+    // Checks each rotation against the last one sent for the same sensor and warns about or rejects (see JumpAction)
+    // ones that would mean turning faster than `max_degrees_per_sec`. Catches garbage from a broken motion pipeline
+    // before the server shows it. Only normal rotations are checked, corrections can jump. Off by default.
+    pub fn rotation_jump_guard(mut self, max_degrees_per_sec: f32, action: JumpAction) -> Self {
+        self.rotation_guard = Some((max_degrees_per_sec.to_radians(), action));
+        self
+    }
+
+    // Whether newer packet formats depend on the server's protocol version, see PacketNegotiation (defaults to Modern)
+    pub fn packet_negotiation(mut self, negotiation: PacketNegotiation) -> Self {
+        self.packet_negotiation = Some(negotiation);
+        self
+    }
+
+    // With this off the tracker neither sends heartbeats every second nor answers the server's, only send_heartbeat
+    // does. Handy for making the server time the tracker out on purpose. On by default.
+    pub fn auto_heartbeat(mut self, enabled: bool) -> Self {
+        self.auto_heartbeat = Some(enabled);
+        self
+    }
+
+    // Time source for the heartbeat timeout, defaults to SystemClock. Tests can pass a MockClock (`testing` feature)
+    // and advance it instead of waiting for the timeout for real.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    // Speak owoTrack instead of the native SlimeVR protocol, see ProtocolMode
+    pub fn protocol_mode(mut self, protocol_mode: ProtocolMode) -> Self {
+        self.protocol_mode = Some(protocol_mode);
+        self
+    }
+
+    // LegacySlimevrTracker makes the tracker act like SlimeVR firmware from before sensor ids, for testing a server's
+    // backward compatibility: the handshake has no MAC address and reports build 8 unless build_number is set (the
+    // server sets up sensor 0 itself for builds before 9), there's no SensorInfo, and sensor 0 is the only one. Its
+    // rotation goes in the legacy rotation packet and acceleration leaves out the sensor id. Newer packets (bundles,
+    // flex data, position) aren't available. The other flags don't change anything yet.
+    pub fn feature_flags(mut self, feature_flags: FirmwareFeatureFlags) -> Self {
+        self.feature_flags = Some(feature_flags);
+        self
+    }
+
+    // Caps rotation/acceleration packets to this many per second for each sensor, like real firmware would.
+    // Updates sent faster than that are dropped and return TrackerError::Throttled, see EmulatedTracker::throttle
+    // for the details. build() fails with TrackerError::InvalidRate unless it's positive.
+    pub fn data_rate_hz(mut self, rate_hz: f32) -> Self {
+        self.data_rate_hz = Some(rate_hz);
+        self
+    }
+
+    // Drops each outgoing datagram with this probability (0-1), for testing how a server copes with bad WiFi.
+    // Handshakes are exempt so the tracker can still connect
+    #[cfg(feature = "testing")]
+    pub fn simulated_loss(mut self, probability: f32) -> Self {
+        self.simulated_loss = Some(probability);
+        self
+    }
+
+    // Holds every outgoing datagram except handshakes back by `latency` before it hits the socket
+    #[cfg(feature = "testing")]
+    pub fn simulated_latency(mut self, latency: Duration) -> Self {
+        self.simulated_latency = Some(latency);
+        self
+    }
+
+    // Heartbeats go out with the current sequence number instead of taking the next one, so the numbers other
+    // packets get only depend on what the test sends (see EmulatedTracker::set_packet_number)
+    #[cfg(feature = "testing")]
+    pub fn freeze_heartbeat_numbering(mut self, enabled: bool) -> Self {
+        self.freeze_heartbeat_numbering = Some(enabled);
+        self
+    }
+
+    pub fn build(self) -> Result<EmulatedTracker, TrackerError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        // Set default values if the options weren't set
+        let mac_address = self.mac_address.unwrap_or_else(random_mac);
+        let firmware_version = self
+            .firmware_version
+            .unwrap_or(concat!("tracker_emulation-rs ", env!("CARGO_PKG_VERSION")).to_string());
+        if firmware_version.len() > MAX_FIRMWARE_VERSION_LEN {
+            return Err(TrackerError::InvalidFirmwareVersion(firmware_version.len()));
+        }
+        let protocol_mode = self.protocol_mode.unwrap_or_default();
+        let (board, mcu) = match protocol_mode {
+            ProtocolMode::SlimeVr => (
+                self.board_type.unwrap_or(BoardType::Unknown(0)),
+                self.mcu_type.unwrap_or(McuType::Unknown(0)),
+            ),
+            ProtocolMode::OwoTrack => (
+                self.board_type.unwrap_or(BoardType::OwoTrack),
+                self.mcu_type.unwrap_or(McuType::OwoTrackAndroid),
+            ),
+        };
+        let feature_flags = self.feature_flags.unwrap_or(FirmwareFeatureFlags::None);
+        let default_build = if feature_flags == FirmwareFeatureFlags::LegacySlimevrTracker {
+            8
+        } else {
+            13
+        };
+        let explicit_server = self.server_ip.is_some();
+        let server_ip = self.server_ip.unwrap_or("255.255.255.255".to_string());
+        let server_port = self.server_discovery_port.unwrap_or(6969);
+        // Resolved once here, hostnames work too but block for the lookup
+        let discovery_addr = (server_ip.as_str(), server_port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or(TrackerError::InvalidServerAddress(server_ip))?;
+        // Broadcast is only best effort for the default address, see bind_socket. Anything else has to be asked for
+        // with broadcast_address or by spelling out 255.255.255.255, a unicast IP can look like a subnet broadcast
+        let require_broadcast = self.broadcast_address.is_some()
+            || (explicit_server
+                && matches!(discovery_addr.ip(), IpAddr::V4(ip) if ip.is_broadcast()));
+        let server_timeout = self.server_timeout_ms.unwrap_or(5000);
+        let timeout_check_interval = self
+            .timeout_check_interval
+            .unwrap_or(Duration::from_millis(server_timeout) / 4)
+            .max(Duration::from_millis(1));
+        let debug = self.debug.unwrap_or(false);
+        let data_interval = self.data_rate_hz.map(rate_interval).transpose()?;
+        let handshake_backoff = match self.handshake_backoff {
+            Some((base, _)) if base.is_zero() => return Err(TrackerError::InvalidHandshakeBackoff),
+            Some((base, max)) => (base, max.max(base)),
+            None => (Duration::from_secs(1), Duration::from_secs(10)),
+        };
+        let recv_buffer_size = self.recv_buffer_size.unwrap_or(1024).max(1);
+
+        let (status_tx, status_rx) = watch::channel(TrackerStatus::Initializing);
+
+        let primary_imu = self.primary_imu.as_ref().map(ImuType::id);
+        let build_number = self.build_number.unwrap_or(default_build);
+        let imu_info = self.imu_info.unwrap_or((0, 0, 0));
+        // The tracker builds the same handshake (see EmulatedTracker::handshake), there just aren't any sensors yet
+        let core = Arc::new(std::sync::Mutex::new(TrackerCore::new(Handshake {
+            board: BoardType::from_id(board.id()),
+            imu: ImuType::from_id(primary_imu.unwrap_or(0)),
+            mcu: McuType::from_id(mcu.id()),
+            imu_info,
+            build: build_number,
+            firmware: firmware_version.clone(),
+            mac_address,
+            legacy: feature_flags == FirmwareFeatureFlags::LegacySlimevrTracker,
+        })));
+        let mut state = TrackerState {
+            status: TrackerStatus::Initializing,
+            packet_number: 0,
+            last_received_packet_time: 0,
+            stats: TrackerStats::default(),
+            acked_sensors: HashSet::new(),
+            server_info: None,
+            server_addr: None,
+            discovered_servers: Vec::new(),
+            send_target: SendTarget::Broadcast,
+            discovery_addr,
+            broadcast_address: self.broadcast_address,
+            history: PacketHistory::new(self.packet_history.unwrap_or(0)),
+            publisher: None,
+        };
+        let state_publisher = Arc::new(StatePublisher {
+            tx: watch::channel(state.snapshot()).0,
+            core: core.clone(),
+        });
+        state.publisher = Some(state_publisher.clone());
+        let state = Arc::new(Mutex::new(state));
+        let status_tx = Arc::new(status_tx);
+        let event_tx = broadcast::channel(32).0;
+        let debug = Arc::new(AtomicBool::new(debug));
+        let send_path = SendPath {
+            state: state.clone(),
+            core,
+            status_tx: status_tx.clone(),
+            status_rx: status_rx.clone(),
+            event_tx: event_tx.clone(),
+            debug: debug.clone(),
+            mac_address,
+            dry_run: self.dry_run.unwrap_or(false),
+            reconnect_after_failures: self.reconnect_after_failures,
+            handshake_backoff,
+            handshake_burst: self.handshake_burst,
+            #[cfg(feature = "testing")]
+            simulated_loss: self.simulated_loss.unwrap_or(0.0).clamp(0.0, 1.0),
+            #[cfg(feature = "testing")]
+            simulated_latency: self.simulated_latency.unwrap_or_default(),
+            #[cfg(feature = "testing")]
+            delayed_sends: Default::default(),
+            recorder: Default::default(),
+        };
+
+        Ok(EmulatedTracker {
+            mac_address,
+            firmware_version,
+            board_id: board.id(),
+            mcu_id: mcu.id(),
+            sensors: Vec::new(),
+            server_timeout,
+            timeout_check_interval,
+            data_port: self.server_data_port,
+            server_selection: self.server_selection.unwrap_or_default(),
+            debug,
+            recv_buffer_size,
+            socket_send_buffer: self.socket_send_buffer,
+            socket_recv_buffer: self.socket_recv_buffer,
+            keep_socket: self.keep_socket_on_deinit.unwrap_or(false),
+            separate_data_socket: self.separate_data_socket.unwrap_or(false),
+            require_broadcast,
+            sensor_info_retry: self.sensor_info_retry,
+            bundle_packets: self.bundle_packets.unwrap_or(false),
+            max_datagram_size: self.max_datagram_size.unwrap_or(DEFAULT_MAX_DATAGRAM_SIZE),
+            packet_negotiation: self.packet_negotiation.unwrap_or_default(),
+            auto_heartbeat: self.auto_heartbeat.unwrap_or(true),
+            paused: AtomicBool::new(false),
+            protocol_mode,
+            feature_flags,
+            clock: self.clock.unwrap_or_else(|| Arc::new(SystemClock)),
+            primary_imu,
+            build_number,
+            imu_info,
+            position_packet_type: self.position_packet_type,
+            data_interval,
+            last_data_sends: Default::default(),
+            last_rotations: Default::default(),
+            rotation_guard: self.rotation_guard,
+            #[cfg(feature = "testing")]
+            freeze_heartbeat_numbering: self.freeze_heartbeat_numbering.unwrap_or(false),
+            socket: None,
+            data_socket: None,
+            state,
+            state_publisher,
+            send_path,
+            status_tx,
+            status_rx,
+            event_tx,
+            incoming_tx: Default::default(),
+            connected_notify: Arc::new(Notify::new()),
+        })
+    }
+}
+
+// Time between updates at `rate_hz`. Anything that isn't positive, or so far off that the interval doesn't fit a
+// Duration or rounds down to nothing, is an error rather than a panic in Duration::from_secs_f32 or interval.
+pub(crate) fn rate_interval(rate_hz: f32) -> Result<Duration, TrackerError> {
+    if !(rate_hz.is_finite() && rate_hz > 0.0) {
+        return Err(TrackerError::InvalidRate(rate_hz));
+    }
+    Duration::try_from_secs_f32(1.0 / rate_hz)
+        .ok()
+        .filter(|interval| !interval.is_zero())
+        .ok_or(TrackerError::InvalidRate(rate_hz))
+}
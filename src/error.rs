@@ -0,0 +1,93 @@
+use std::fmt;
+use std::net::Ipv4Addr;
+
+// Firmware strings are length-prefixed with a single byte
+pub const MAX_FIRMWARE_VERSION_LEN: usize = 255;
+
+pub const MAX_SENSORS: usize = u8::MAX as usize + 1;
+
+// Well above a fully charged cell or USB power, anything higher is a bug in the caller
+pub const MAX_BATTERY_VOLTAGE: f32 = 6.0;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TrackerError {
+    InvalidMacAddress(String),
+    // Length in bytes of the rejected version string
+    InvalidFirmwareVersion(usize),
+    // Sensor ids are a u8, so there's no id left for another sensor
+    TooManySensors,
+    DuplicateSensorId(u8),
+    // A sensor id no sensor was added with
+    UnknownSensor(u8),
+    // Board/MCU/IMU name that doesn't match any known hardware
+    UnknownHardwareName(String),
+    // Something that needs the socket was called before init
+    NotInitialized,
+    // try_deinit on a tracker that isn't running
+    NotConnected,
+    InvalidBroadcastAddress(Ipv4Addr),
+    // Percentage that isn't a number, or a voltage outside 0-MAX_BATTERY_VOLTAGE
+    InvalidBattery { percentage: f32, voltage: f32 },
+    // server_ip that isn't an IP address and didn't resolve either
+    InvalidServerAddress(String),
+    // A rate in Hz that isn't a positive, finite number
+    InvalidRate(f32),
+    // handshake_backoff with a zero base, the tracker would handshake nonstop
+    InvalidHandshakeBackoff,
+    // Data for this sensor dropped by data_rate_hz, its last update went out less than an interval ago
+    Throttled(u8),
+}
+
+impl fmt::Display for TrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackerError::InvalidMacAddress(mac) => write!(f, "Invalid MAC address: {mac}"),
+            TrackerError::InvalidFirmwareVersion(len) => write!(
+                f,
+                "Firmware version is {len} bytes long, the maximum is {MAX_FIRMWARE_VERSION_LEN}"
+            ),
+            TrackerError::TooManySensors => write!(
+                f,
+                "A tracker can have at most {MAX_SENSORS} sensors (ids 0-255)"
+            ),
+            TrackerError::DuplicateSensorId(id) => {
+                write!(f, "There's already a sensor with id {id}")
+            }
+            TrackerError::UnknownSensor(id) => write!(f, "No sensor with id {id}"),
+            TrackerError::UnknownHardwareName(name) => write!(f, "Unknown hardware name: {name}"),
+            TrackerError::InvalidBroadcastAddress(addr) => {
+                write!(f, "{addr} isn't a broadcast address")
+            }
+            TrackerError::InvalidBattery {
+                percentage,
+                voltage,
+            } => write!(
+                f,
+                "Invalid battery level {percentage}% at {voltage}V, voltage must be 0-{MAX_BATTERY_VOLTAGE}V"
+            ),
+            TrackerError::InvalidServerAddress(addr) => write!(f, "Invalid server address: {addr}"),
+            TrackerError::InvalidRate(rate_hz) => {
+                write!(f, "Invalid rate {rate_hz}Hz, it has to be a positive number")
+            }
+            TrackerError::InvalidHandshakeBackoff => {
+                write!(f, "Handshake backoff has to start above zero")
+            }
+            TrackerError::Throttled(id) => write!(
+                f,
+                "Dropped data for sensor {id}, it's past data_rate_hz"
+            ),
+            TrackerError::NotInitialized => write!(f, "Tracker isn't initialized, call init first"),
+            TrackerError::NotConnected => write!(f, "Tracker isn't running, there's nothing to deinit"),
+        }
+    }
+}
+
+impl std::error::Error for TrackerError {}
+
+// Most of the API still reports errors as strings
+impl From<TrackerError> for String {
+    fn from(error: TrackerError) -> Self {
+        error.to_string()
+    }
+}
@@ -0,0 +1,79 @@
+// Things that happen to a tracker over its lifetime, see EmulatedTracker::subscribe_events
+
+use std::net::SocketAddr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TrackerEvent {
+    // The server answered our handshake, `version` is what it reported about itself
+    HandshakeComplete { version: String },
+    // Lost the server. After a heartbeat timeout the tracker needs another init to reconnect, after repeated send
+    // failures (see reconnect_after_failures) it goes looking for the server again by itself.
+    Disconnected,
+    // The server asked the tracker to do something. There's no server-side user action (resets are done by the
+    // server itself), commands are the only requests it sends.
+    Command(ServerCommand),
+    // A server we hadn't heard from before sent something, lists every server seen since init (see
+    // server_selection for which one the tracker uses)
+    ServersDiscovered(Vec<SocketAddr>),
+}
+
+// Commands the server can send, same ids as the firmware
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ServerCommand {
+    Calibrate,
+    SendConfig,
+    Blink,
+    Unknown(u8),
+}
+
+impl From<u8> for ServerCommand {
+    fn from(id: u8) -> Self {
+        match id {
+            1 => ServerCommand::Calibrate,
+            2 => ServerCommand::SendConfig,
+            3 => ServerCommand::Blink,
+            _ => ServerCommand::Unknown(id),
+        }
+    }
+}
+
+// What we know about the server we're connected to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub version: String,
+    // The number the server puts at the end of its version string (the "5" in "Hey OVR =D 5"), None if there isn't
+    // one
+    pub protocol_version: Option<u32>,
+}
+
+impl ServerInfo {
+    pub fn new(version: String) -> Self {
+        let protocol_version = version
+            .rsplit(|c: char| !c.is_ascii_digit())
+            .next()
+            .and_then(|digits| digits.parse().ok());
+        Self {
+            version,
+            protocol_version,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_version() {
+        assert_eq!(
+            ServerInfo::new("Hey OVR =D 5".to_string()).protocol_version,
+            Some(5)
+        );
+        assert_eq!(
+            ServerInfo::new("Hey OVR =D".to_string()).protocol_version,
+            None
+        );
+    }
+}
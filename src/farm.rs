@@ -0,0 +1,221 @@
+use crate::{EmulatedTracker, TrackerEvent, TrackerStatus, Vec3};
+use firmware_protocol::{SensorDataType, SlimeQuaternion};
+use futures::future::join_all;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+// Owns a group of emulated trackers, mostly useful for stress-testing a server with lots of trackers
+pub struct TrackerFarm {
+    trackers: Vec<EmulatedTracker>,
+}
+
+impl TrackerFarm {
+    // Creates `count` trackers with default settings, the MAC addresses count up from `base_mac`
+    pub async fn new(
+        count: usize,
+        base_mac: [u8; 6],
+        firmware_version: String,
+        server_ip: Option<String>,
+        server_discovery_port: Option<u16>,
+        server_timeout_ms: Option<u64>,
+    ) -> Result<Self, String> {
+        let mut trackers = Vec::with_capacity(count);
+        for i in 0..count {
+            let mut builder = EmulatedTracker::builder()
+                .mac_address(offset_mac(base_mac, i as u64))
+                .firmware_version(firmware_version.clone());
+            if let Some(server_ip) = &server_ip {
+                builder = builder.server_ip(server_ip.clone());
+            }
+            if let Some(port) = server_discovery_port {
+                builder = builder.server_discovery_port(port);
+            }
+            if let Some(timeout_ms) = server_timeout_ms {
+                builder = builder.server_timeout_ms(timeout_ms);
+            }
+            trackers.push(builder.build()?);
+        }
+        Ok(Self { trackers })
+    }
+
+    pub fn from_trackers(trackers: Vec<EmulatedTracker>) -> Self {
+        Self { trackers }
+    }
+
+    pub fn add_tracker(&mut self, tracker: EmulatedTracker) -> usize {
+        self.trackers.push(tracker);
+        self.trackers.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.trackers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trackers.is_empty()
+    }
+
+    pub fn tracker(&self, tracker_idx: usize) -> Option<&EmulatedTracker> {
+        self.trackers.get(tracker_idx)
+    }
+
+    pub fn tracker_mut(&mut self, tracker_idx: usize) -> Option<&mut EmulatedTracker> {
+        self.trackers.get_mut(tracker_idx)
+    }
+
+    pub fn trackers(&self) -> &[EmulatedTracker] {
+        &self.trackers
+    }
+
+    /*
+     * Lifecycle functions
+     */
+
+    // Inits every tracker concurrently, returns the first error if any of them failed
+    pub async fn init_all(&mut self) -> Result<(), String> {
+        let results = join_all(self.trackers.iter_mut().map(|tracker| tracker.init())).await;
+        results.into_iter().collect()
+    }
+
+    pub async fn deinit_all(&mut self) -> Result<(), String> {
+        let results = join_all(self.trackers.iter_mut().map(|tracker| tracker.deinit())).await;
+        results.into_iter().collect()
+    }
+
+    /*
+     * Status functions
+     */
+
+    pub fn statuses(&self) -> Vec<TrackerStatus> {
+        self.trackers
+            .iter()
+            .map(|tracker| *tracker.subscribe_status().borrow())
+            .collect()
+    }
+
+    // Merges the status channels of all trackers into one, each update is tagged with the tracker index
+    pub fn subscribe_status(&self) -> UnboundedReceiver<(usize, TrackerStatus)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for (tracker_idx, tracker) in self.trackers.iter().enumerate() {
+            let mut status_rx = tracker.subscribe_status();
+            let tx = tx.clone();
+            tracker.spawn_task("farm_status", async move {
+                while status_rx.changed().await.is_ok() {
+                    let status = *status_rx.borrow_and_update();
+                    if tx.send((tracker_idx, status)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        rx
+    }
+
+    // Same for the events of all trackers. A tracker whose events pile up past what subscribe_events holds skips the
+    // oldest ones, like a single subscriber would.
+    pub fn subscribe_events(&self) -> UnboundedReceiver<(usize, TrackerEvent)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for (tracker_idx, tracker) in self.trackers.iter().enumerate() {
+            let mut events = tracker.subscribe_events();
+            let tx = tx.clone();
+            tracker.spawn_task("farm_events", async move {
+                loop {
+                    let event = match events.recv().await {
+                        Ok(event) => event,
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    };
+                    if tx.send((tracker_idx, event)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        rx
+    }
+
+    /*
+     * Tracker functions
+     */
+
+    pub async fn send_rotation(
+        &self,
+        tracker_idx: usize,
+        sensor_id: u8,
+        data_type: SensorDataType,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+    ) -> Result<u64, String> {
+        self.get(tracker_idx)?
+            .send_rotation(sensor_id, data_type, rotation_data, accuracy)
+            .await
+    }
+
+    pub async fn send_acceleration(
+        &self,
+        tracker_idx: usize,
+        sensor_id: u8,
+        acceleration: impl Into<Vec3>,
+    ) -> Result<u64, String> {
+        self.get(tracker_idx)?
+            .send_acceleration(sensor_id, acceleration)
+            .await
+    }
+
+    fn get(&self, tracker_idx: usize) -> Result<&EmulatedTracker, String> {
+        self.trackers
+            .get(tracker_idx)
+            .ok_or_else(|| format!("No tracker at index {tracker_idx}"))
+    }
+}
+
+// Treats the MAC as a 48-bit number and adds `offset` to it
+fn offset_mac(base_mac: [u8; 6], offset: u64) -> [u8; 6] {
+    let mut bytes = [0u8; 8];
+    bytes[2..].copy_from_slice(&base_mac);
+    let value = u64::from_be_bytes(bytes).wrapping_add(offset) & 0xFFFF_FFFF_FFFF;
+    let mut mac = [0u8; 6];
+    mac.copy_from_slice(&value.to_be_bytes()[2..]);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::ServerCommand;
+    use crate::sans_io::PACKET_COMMAND;
+
+    #[tokio::test]
+    async fn test_subscribe_events() {
+        let trackers = (0..2)
+            .map(|_| EmulatedTracker::builder().dry_run(true).build().unwrap())
+            .collect();
+        let mut farm = TrackerFarm::from_trackers(trackers);
+        farm.init_all().await.unwrap();
+        let mut events = farm.subscribe_events();
+
+        // Blink command to the second tracker
+        let mut datagram = PACKET_COMMAND.to_be_bytes().to_vec();
+        datagram.extend_from_slice(&1u64.to_be_bytes());
+        datagram.push(3);
+        farm.tracker(1)
+            .unwrap()
+            .handle_packet(&datagram)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            events.recv().await.unwrap(),
+            (1, TrackerEvent::Command(ServerCommand::Blink))
+        );
+        farm.deinit_all().await.unwrap();
+    }
+
+    #[test]
+    fn test_offset_mac() {
+        let base = [0xDE, 0xAD, 0xBE, 0xEF, 0x00, 0xFF];
+        assert_eq!(offset_mac(base, 0), base);
+        assert_eq!(offset_mac(base, 1), [0xDE, 0xAD, 0xBE, 0xEF, 0x01, 0x00]);
+        assert_eq!(offset_mac([0xFF; 6], 1), [0x00; 6]);
+    }
+}
@@ -1,22 +1,455 @@
-use firmware_protocol::deku::prelude::*;
+#[cfg(feature = "tokio")]
+#[macro_use]
+mod logging;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "tokio")]
+mod builder;
+pub mod clock;
+mod error;
+mod event;
+#[cfg(feature = "tokio")]
+pub mod farm;
+pub mod mac;
+mod math;
+#[cfg(feature = "tokio")]
+pub mod motion;
+pub mod names;
+pub mod net;
+#[cfg(feature = "tokio")]
+pub mod noise;
+#[cfg(feature = "tokio")]
+mod receive;
+#[cfg(feature = "tokio")]
+pub mod record;
+pub mod sans_io;
+#[cfg(feature = "tokio")]
+pub mod simulation;
+
+#[cfg(feature = "tokio")]
+pub use builder::EmulatedTrackerBuilder;
+#[cfg(feature = "testing")]
+pub use clock::MockClock;
+pub use clock::{Clock, SystemClock};
+pub use error::{TrackerError, MAX_BATTERY_VOLTAGE, MAX_FIRMWARE_VERSION_LEN, MAX_SENSORS};
+pub use event::{ServerCommand, ServerInfo, TrackerEvent};
+#[cfg(feature = "tokio")]
+pub use farm::TrackerFarm;
+pub use mac::{mac_from_str, mac_to_string, random_mac};
+pub use math::{Quat, Vec3};
+pub use net::subnet_broadcast;
+#[cfg(feature = "tokio")]
+pub use record::{replay, Recorder};
+
+#[cfg(feature = "tokio")]
 use firmware_protocol::{
-    ActionType, BoardType, CbPacket, ImuType, McuType, Packet, SbPacket, SensorDataType,
-    SensorStatus, SlimeQuaternion,
+    ActionType, BoardType, CbPacket, ImuType, McuType, SbPacket, SensorDataType, SensorStatus,
+    SlimeQuaternion,
 };
+#[cfg(feature = "tokio")]
+use futures::Stream;
+#[cfg(feature = "tokio")]
+use logging::{HexDump, PacketHistory};
+#[cfg(feature = "tokio")]
+use names::HardwareId;
+#[cfg(feature = "tokio")]
+use receive::PacketHandler;
+#[cfg(feature = "tokio")]
+use sans_io::{Handshake, RawPacket, TrackerCore};
+#[cfg(feature = "tokio")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "tokio")]
+use std::fmt;
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+#[cfg(feature = "tokio")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "tokio")]
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+#[cfg(feature = "tokio")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "tokio")]
 use tokio::net::UdpSocket;
-use tokio::sync::watch::{self, Receiver, Sender};
-use tokio::sync::Mutex;
-use tokio::time::{interval, sleep};
+#[cfg(feature = "tokio")]
+use tokio::sync::watch::{Receiver, Sender};
+#[cfg(feature = "tokio")]
+use tokio::sync::{broadcast, mpsc};
+#[cfg(feature = "tokio")]
+use tokio::sync::{Mutex, Notify};
+#[cfg(feature = "tokio")]
+use tokio::task::JoinHandle;
+#[cfg(feature = "tokio")]
+use tokio::time::{sleep, sleep_until};
+
+// Packets incoming holds on to while nobody polls the stream
+#[cfg(feature = "tokio")]
+pub const INCOMING_CAPACITY: usize = 64;
+
+// Shared with the receive task, None until incoming is called
+#[cfg(feature = "tokio")]
+type IncomingSender = Arc<std::sync::Mutex<Option<mpsc::Sender<CbPacket>>>>;
+
+// Which flavour of the protocol the tracker speaks
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProtocolMode {
+    #[default]
+    SlimeVr,
+    // The subset SlimeVR accepts from the owoTrack phone app: one sensor, rotation in the legacy packet without a
+    // sensor id, and no SensorInfo. Board and MCU default to the owoTrack ones.
+    OwoTrack,
+}
+
+// Which server to connect to when more than one answers
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ServerSelection {
+    // Whichever answers first
+    #[default]
+    First,
+    // Only the server at this IP, everyone else is ignored
+    Address(IpAddr),
+}
+
+#[cfg(feature = "tokio")]
+impl ServerSelection {
+    fn accepts(&self, addr: SocketAddr) -> bool {
+        match self {
+            ServerSelection::First => true,
+            ServerSelection::Address(ip) => addr.ip() == *ip,
+        }
+    }
+}
+
+// Whether to use packets older servers may not understand (bundles, flex data, position). Servers just drop
+// packets they can't parse, so without this an old server silently misses data.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PacketNegotiation {
+    // Modern until the server answers the handshake, then Legacy if it reports a protocol version older than this
+    // one. Servers that don't report one are treated as modern. No protocol version is documented as the one that
+    // added these packets, so the cutoff has to come from the servers being targeted.
+    MinProtocolVersion(u32),
+    // Always use whatever the tracker is configured for
+    #[default]
+    Modern,
+    // Never bundle, and send_flex_data/send_position return an error
+    Legacy,
+}
+
+// 1500 byte MTU minus the IPv4 (20) and UDP (8) headers
+#[cfg(feature = "tokio")]
+pub const DEFAULT_MAX_DATAGRAM_SIZE: usize = 1472;
 
+// Where packets are currently going, see EmulatedTracker::send_target
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SendTarget {
+    // Still discovering on a broadcast address, nobody has answered yet
+    Broadcast,
+    // Straight to the server, or to a configured unicast server_ip before it answered
+    Unicast(SocketAddr),
+}
+
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TrackerStatus {
+    // Not started, or stopped by deinit
+    Initializing,
+    // Looking for a server
+    Idle,
+    Connected,
+    // Stopped because the server stopped sending heartbeats, init again to reconnect. Unlike Initializing this
+    // means there was a server at some point.
+    Disconnected,
+}
+
+#[cfg(feature = "tokio")]
+impl TrackerStatus {
+    // Background tasks shut down in these, and init can start the tracker again
+    fn is_stopped(self) -> bool {
+        matches!(
+            self,
+            TrackerStatus::Initializing | TrackerStatus::Disconnected
+        )
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl fmt::Display for TrackerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TrackerStatus::Initializing => "initializing",
+            TrackerStatus::Idle => "idle",
+            TrackerStatus::Connected => "connected-to-server",
+            TrackerStatus::Disconnected => "disconnected",
+        })
+    }
+}
+
+#[cfg(feature = "tokio")]
 #[derive(Clone)]
 pub struct TrackerState {
-    pub status: String,
+    pub status: TrackerStatus,
+    // Only kept up to date in what get_state returns, see packet_number()
     pub packet_number: u64,
     pub last_received_packet_time: u16,
+    pub stats: TrackerStats,
+    // Sensors the server confirmed with its own SensorInfo packet
+    pub acked_sensors: HashSet<u8>,
+    // Filled in once the server answers the handshake
+    pub server_info: Option<ServerInfo>,
+    // Where the server answered from (with the data port override applied), None until it has
+    pub server_addr: Option<SocketAddr>,
+    // Every server that has sent us something since init (one per IP), including ones server_selection passed on
+    pub discovered_servers: Vec<SocketAddr>,
+    // Only kept up to date in what get_state returns, see send_target()
+    pub send_target: SendTarget,
+    // Where handshakes go, the builder's server address until set_server changes it
+    pub discovery_addr: SocketAddr,
+    // From the builder's broadcast_address, the only address besides 255.255.255.255 that counts as a broadcast
+    pub(crate) broadcast_address: Option<Ipv4Addr>,
+    pub(crate) history: PacketHistory,
+    // None in snapshots, a snapshot holding the sender would keep its own channel open
+    pub(crate) publisher: Option<Arc<StatePublisher>>,
+}
+
+// Feeds subscribe_state. Holds the core too since snapshots fill in packet_number.
+#[cfg(feature = "tokio")]
+pub(crate) struct StatePublisher {
+    pub(crate) tx: Sender<TrackerState>,
+    pub(crate) core: Arc<std::sync::Mutex<TrackerCore>>,
+}
+
+#[cfg(feature = "tokio")]
+#[derive(Clone, Debug, Default)]
+pub struct TrackerStats {
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub bytes_sent: u64,
+    pub reconnects: u64,
+    // Time between our last heartbeat and the next heartbeat received from the server
+    pub last_heartbeat_rtt: Option<Duration>,
+    // Round trip of the last ping we sent with send_ping
+    pub last_ping_rtt: Option<Duration>,
+    // Time taken to echo the last ping the server sent us
+    pub last_ping_response_time: Option<Duration>,
+    // When the last packet actually went out, the protocol has no timestamps so this is what the server sees
+    pub last_sent_at: Option<Instant>,
+    // Rotation/acceleration sends dropped by the data rate limit
+    pub throttled_packets: u64,
+    // Datagrams from the server that didn't parse, past the first few they're only logged now and then
+    pub malformed_packets: u64,
+    last_heartbeat_sent: Option<Instant>,
+    last_heartbeat_received: Option<Instant>,
+    // By the tracker's clock, for uptime and connected_duration
+    initialized_at: Option<Instant>,
+    connected_at: Option<Instant>,
+    last_malformed_log: Option<Instant>,
+    consecutive_send_failures: u32,
+    pending_ping: Option<([u8; 4], Instant)>,
+    ping_rtt_total: Duration,
+    ping_rtt_samples: u32,
+}
+
+// Which frame an acceleration passed to send_acceleration_in is in. send_acceleration always takes SensorLocal,
+// which is what the firmware sends, and passes it through untouched.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AccelFrame {
+    #[default]
+    SensorLocal,
+    // Same axes as the rotations (e.g. motion capture data), rotated into the sensor's frame using the last
+    // rotation sent for that sensor
+    World,
+}
+
+// What rotation_jump_guard does with a rotation that turned faster than the limit
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JumpAction {
+    // Log a warning and send it anyway
+    #[default]
+    Warn,
+    // Don't send it, the send returns an error
+    Reject,
+}
+
+// Configuration of a tracker, see config_snapshot
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct TrackerConfig {
+    pub mac_address: [u8; 6],
+    pub firmware_version: String,
+    pub board_type: BoardType,
+    pub mcu_type: McuType,
+    pub protocol_mode: ProtocolMode,
+    pub feature_flags: FirmwareFeatureFlags,
+    pub build_number: u32,
+    // Where handshakes go, set_server changes this too. Data may go elsewhere once connected (see
+    // TrackerState::server_addr).
+    pub server_addr: SocketAddr,
+    pub data_port: Option<u16>,
+    pub server_timeout: Duration,
+    pub sensor_count: usize,
+}
+
+// The protocol enums don't implement Clone, so this goes through their wire ids
+#[cfg(feature = "tokio")]
+impl Clone for TrackerConfig {
+    fn clone(&self) -> Self {
+        Self {
+            mac_address: self.mac_address,
+            firmware_version: self.firmware_version.clone(),
+            board_type: BoardType::from_id(self.board_type.id()),
+            mcu_type: McuType::from_id(self.mcu_type.id()),
+            protocol_mode: self.protocol_mode,
+            feature_flags: self.feature_flags,
+            build_number: self.build_number,
+            server_addr: self.server_addr,
+            data_port: self.data_port,
+            server_timeout: self.server_timeout,
+            sensor_count: self.sensor_count,
+        }
+    }
+}
+
+// Snapshot from health_check, for supervisors deciding whether to restart the tracker
+#[cfg(feature = "tokio")]
+#[derive(Clone, Debug)]
+pub struct HealthReport {
+    pub socket_bound: bool,
+    pub connected: bool,
+    // Time since the server's last heartbeat, None if it never connected
+    pub last_heartbeat_age: Option<Duration>,
+    // Connected and the last heartbeat is within server_timeout_ms
+    pub heartbeats_flowing: bool,
+    pub sensors_registered: usize,
+    pub sensors_acknowledged: usize,
+}
+
+#[cfg(feature = "tokio")]
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.socket_bound && self.connected && self.heartbeats_flowing
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl TrackerState {
+    // Every status change goes through here so the state and the status channel always agree, the caller holding
+    // the state lock makes the two updates atomic
+    fn set_status(&mut self, status: TrackerStatus, status_tx: &Sender<TrackerStatus>) {
+        self.status = status;
+        if status != TrackerStatus::Connected {
+            self.stats.connected_at = None;
+        }
+        if status == TrackerStatus::Initializing {
+            self.stats.initialized_at = None;
+        }
+        // The tracker keeps a receiver itself, but background tasks may outlive it
+        let _ = status_tx.send(status);
+        self.publish();
+    }
+
+    // Counts a datagram that went out and keeps it for last_sent
+    fn record_sent(&mut self, bytes: &[u8]) {
+        self.stats.record_sent(bytes.len());
+        self.history.push_sent(bytes);
+        self.publish();
+    }
+
+    // What get_state and subscribe_state hand out, with the fields that are only kept up to date there filled in.
+    // The packet history stays behind, it's only for last_sent/last_received.
+    fn snapshot(&self) -> TrackerState {
+        TrackerState {
+            status: self.status,
+            packet_number: self
+                .publisher
+                .as_ref()
+                .map_or(self.packet_number, |publisher| {
+                    publisher.core.lock().unwrap().packet_number()
+                }),
+            last_received_packet_time: self.last_received_packet_time,
+            stats: self.stats.clone(),
+            acked_sensors: self.acked_sensors.clone(),
+            server_info: self.server_info.clone(),
+            server_addr: self.server_addr,
+            discovered_servers: self.discovered_servers.clone(),
+            send_target: self.send_target(),
+            discovery_addr: self.discovery_addr,
+            broadcast_address: self.broadcast_address,
+            history: PacketHistory::new(0),
+            publisher: None,
+        }
+    }
+
+    // Hands a snapshot to subscribe_state, skipping the copy while nobody is subscribed
+    pub(crate) fn publish(&self) {
+        let Some(publisher) = self.publisher.as_ref() else {
+            return;
+        };
+        if publisher.tx.receiver_count() > 0 {
+            publisher.tx.send_replace(self.snapshot());
+        }
+    }
+
+    // Handshakes always go to the discovery address, everything else goes where the server answered from once it
+    // has. Servers can listen for handshakes on one port and take data on another.
+    fn data_target(&self) -> SocketAddr {
+        self.server_addr.unwrap_or(self.discovery_addr)
+    }
+
+    // Once there's a server everything goes straight to it. Before that it depends on the discovery address, and
+    // without the netmask there's no telling a subnet broadcast from a host, so only the limited broadcast address
+    // and one set with broadcast_address count.
+    fn send_target(&self) -> SendTarget {
+        if let Some(server_addr) = self.server_addr {
+            return SendTarget::Unicast(server_addr);
+        }
+        match self.discovery_addr.ip() {
+            IpAddr::V4(ip) if ip.is_broadcast() || self.broadcast_address == Some(ip) => {
+                SendTarget::Broadcast
+            }
+            _ => SendTarget::Unicast(self.discovery_addr),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl TrackerStats {
+    fn record_sent(&mut self, size: usize) {
+        self.packets_sent += 1;
+        self.bytes_sent += size as u64;
+        self.last_sent_at = Some(Instant::now());
+    }
+
+    // When the server last sent a heartbeat (or connected, until the first one arrives)
+    pub fn last_heartbeat_received(&self) -> Option<Instant> {
+        self.last_heartbeat_received
+    }
+
+    pub fn average_ping_rtt(&self) -> Option<Duration> {
+        if self.ping_rtt_samples == 0 {
+            return None;
+        }
+        Some(self.ping_rtt_total / self.ping_rtt_samples)
+    }
 }
 
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum FirmwareFeatureFlags {
     LegacySlimevrTracker = 0,
     RotationData = 1,
@@ -24,35 +457,208 @@ pub enum FirmwareFeatureFlags {
     None = 9999,
 }
 
+#[cfg(feature = "tokio")]
+impl From<FirmwareFeatureFlags> for u32 {
+    fn from(flag: FirmwareFeatureFlags) -> Self {
+        flag as u32
+    }
+}
+
+// Gives back the value if it isn't a known flag
+#[cfg(feature = "tokio")]
+impl TryFrom<u32> for FirmwareFeatureFlags {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(FirmwareFeatureFlags::LegacySlimevrTracker),
+            1 => Ok(FirmwareFeatureFlags::RotationData),
+            2 => Ok(FirmwareFeatureFlags::PositionData),
+            9999 => Ok(FirmwareFeatureFlags::None),
+            _ => Err(value),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum DataKind {
+    Rotation,
+    Correction,
+    Acceleration,
+}
+
+#[cfg(feature = "tokio")]
+impl DataKind {
+    fn of(data_type: &SensorDataType) -> Self {
+        if matches!(data_type, SensorDataType::Correction) {
+            DataKind::Correction
+        } else {
+            DataKind::Rotation
+        }
+    }
+
+    // SensorDataType isn't Clone either, so rotations carry their kind around instead
+    fn data_type(self) -> SensorDataType {
+        match self {
+            DataKind::Correction => SensorDataType::Correction,
+            DataKind::Rotation | DataKind::Acceleration => SensorDataType::Normal,
+        }
+    }
+}
+
+// Waits between handshakes while no server answers: the burst first, then exponential backoff
+#[cfg(feature = "tokio")]
+struct HandshakeSchedule {
+    burst_left: u32,
+    spacing: Duration,
+    delay: Duration,
+    cap: Duration,
+}
+
+#[cfg(feature = "tokio")]
+impl HandshakeSchedule {
+    fn next_delay(&mut self) -> Duration {
+        if self.burst_left > 0 {
+            self.burst_left -= 1;
+            return self.spacing;
+        }
+        let delay = self.delay;
+        self.delay = (delay * 2).min(self.cap);
+        delay
+    }
+}
+
+// Which socket a packet goes out on, Data only differs from Control with separate_data_socket
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Control,
+    Data,
+}
+
+#[cfg(feature = "tokio")]
+impl Channel {
+    // For packets that are already serialized (replays), going by the packet type. Bundles can hold anything, so
+    // they stay on the control socket.
+    fn of_packet_type(packet_type: u32) -> Self {
+        match packet_type {
+            sans_io::PACKET_ROTATION_LEGACY
+            | sans_io::PACKET_ACCELERATION
+            | sans_io::PACKET_ROTATION_DATA => Channel::Data,
+            _ => Channel::Control,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
 #[derive(Debug)]
 pub struct Sensor {
     pub sensor_id: u8,
-    pub sensor_type: ImuType,
+    // Wire id of the ImuType, see names::HardwareId
+    pub imu_id: u8,
+    // firmware_protocol only has Ok and Offline. Real firmware can also report an error state (2) that it can't
+    // represent yet, and there's no "loading"/"calibrating" status on the wire at all, a calibrating sensor just
+    // reports Ok and sends nothing useful yet. Use Offline for a sensor that isn't ready.
     pub sensor_status: SensorStatus,
 }
 
+#[cfg(feature = "tokio")]
+impl Sensor {
+    fn info_packet(&self) -> SbPacket {
+        SbPacket::SensorInfo {
+            sensor_id: self.sensor_id,
+            sensor_type: ImuType::from_id(self.imu_id),
+            sensor_status: match self.sensor_status {
+                SensorStatus::Ok => SensorStatus::Ok,
+                SensorStatus::Offline => SensorStatus::Offline,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
 pub struct EmulatedTracker {
     // Configuration
     mac_address: [u8; 6],
     firmware_version: String,
-    //feature_flags: FirmwareFeatureFlags,
-    board_type: BoardType,
-    mcu_type: McuType,
+    // Wire ids of the BoardType and McuType, see names::HardwareId
+    board_id: u16,
+    mcu_id: u16,
     server_timeout: u64,
-    server_ip: String,
-    server_port: u16,
-    debug: bool,
+    timeout_check_interval: Duration,
+    // Port to send data to instead of the one the server answers from
+    data_port: Option<u16>,
+    server_selection: ServerSelection,
+    // Shared with the background tasks so set_debug reaches them too
+    debug: Arc<AtomicBool>,
+    recv_buffer_size: usize,
+    // SO_SNDBUF/SO_RCVBUF, None keeps the OS default
+    socket_send_buffer: Option<usize>,
+    socket_recv_buffer: Option<usize>,
+    // Keeps the socket (and its local port) through deinit so the next init reuses it
+    keep_socket: bool,
+    separate_data_socket: bool,
+    // A broadcast address was set on the builder, so a socket that can't broadcast is no use
+    require_broadcast: bool,
+    // Resend interval and retry cap for SensorInfo until the server acknowledges it, None sends it once
+    sensor_info_retry: Option<(Duration, u32)>,
+    // Time source for the heartbeat watchdog
+    clock: Arc<dyn Clock>,
+    bundle_packets: bool,
+    // Bundles get split to stay under this many bytes
+    max_datagram_size: usize,
+    packet_negotiation: PacketNegotiation,
+    auto_heartbeat: bool,
+    // Set by pause, drops rotation/acceleration data until resume
+    paused: AtomicBool,
+    protocol_mode: ProtocolMode,
+    feature_flags: FirmwareFeatureFlags,
+    // IMU reported in the handshake, falls back to the first sensor's type when not set (see the builder for why
+    // that misses the first handshake)
+    primary_imu: Option<u8>,
+    build_number: u32,
+    imu_info: (u32, u32, u32),
+    // Not in the official protocol, None until the builder sets one
+    position_packet_type: Option<u32>,
+    // Minimum time between rotation/acceleration packets for the same sensor, None sends everything
+    data_interval: Option<Duration>,
+    // When each sensor last sent each kind of data
+    last_data_sends: std::sync::Mutex<HashMap<(u8, DataKind), Instant>>,
+    // Last normal rotation that went out for each sensor, for AccelFrame::World and last_rotation
+    last_rotations: std::sync::Mutex<HashMap<u8, (Quat, Instant)>>,
+    // Fastest believable rotation as radians per second and what to do past it, None checks nothing
+    rotation_guard: Option<(f32, JumpAction)>,
+    #[cfg(feature = "testing")]
+    freeze_heartbeat_numbering: bool,
 
     sensors: Vec<Sensor>,
 
     // Socket stuff
     state: Arc<Mutex<TrackerState>>,
+    // Same one the state holds, so subscribe_state doesn't need the lock
+    state_publisher: Arc<StatePublisher>,
+    // Everything sent goes through here, the background tasks get a clone
+    send_path: SendPath,
     socket: Option<Arc<UdpSocket>>,
-    status_tx: Sender<String>,
-    status_rx: Receiver<String>,
+    // Rotation/acceleration go out on this one when separate_data_socket is on, never received on
+    data_socket: Option<Arc<UdpSocket>>,
+    status_tx: Arc<Sender<TrackerStatus>>,
+    status_rx: Receiver<TrackerStatus>,
+    event_tx: broadcast::Sender<TrackerEvent>,
+    // Feeds the stream from incoming, only one at a time
+    incoming_tx: IncomingSender,
+    // Woken every time the tracker goes from not connected to connected
+    connected_notify: Arc<Notify>,
 }
 
+#[cfg(feature = "tokio")]
 impl EmulatedTracker {
+    pub fn builder() -> EmulatedTrackerBuilder {
+        EmulatedTrackerBuilder::new()
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         mac_address: [u8; 6],
         firmware_version: String,
@@ -64,43 +670,116 @@ impl EmulatedTracker {
         server_timeout_ms: Option<u64>,
         debug: Option<bool>,
     ) -> Result<Self, String> {
-        // Set default values if the parameters are None
-        //let feature_flags = feature_flags.unwrap_or(FirmwareFeatureFlags::None);
-        let board_type = board_type.unwrap_or(BoardType::Unknown(0));
-        let mcu_type = mcu_type.unwrap_or(McuType::Unknown(0));
-        let server_ip = server_ip.unwrap_or("255.255.255.255".to_string());
-        let server_port = server_discovery_port.unwrap_or(6969);
-        let server_timeout = server_timeout_ms.unwrap_or(5000);
-        let debug = debug.unwrap_or(false);
-
-        let (status_tx, status_rx) = watch::channel("initializing".to_string());
-
-        let state = Arc::new(Mutex::new(TrackerState {
-            status: "initializing".to_string(),
-            packet_number: 0,
-            last_received_packet_time: 0,
-        }));
-
-        Ok(Self {
-            mac_address,
-            firmware_version,
-            //feature_flags,
+        let builder = EmulatedTrackerBuilder {
+            mac_address: Some(mac_address),
+            firmware_version: Some(firmware_version),
             board_type,
             mcu_type,
-            sensors: Vec::new(),
-            server_timeout,
             server_ip,
-            server_port,
+            server_discovery_port,
+            server_timeout_ms,
             debug,
-            socket: None,
-            state,
-            status_tx,
-            status_rx,
-        })
+            ..Default::default()
+        };
+        Ok(builder.build()?)
     }
 
     pub async fn get_state(&self) -> TrackerState {
-        self.state.lock().await.clone()
+        self.state.lock().await.snapshot()
+    }
+
+    // Whether packets still go out as broadcasts, a tracker stuck on Broadcast never finished discovery
+    pub async fn send_target(&self) -> SendTarget {
+        self.state.lock().await.send_target()
+    }
+
+    // How long ago the server last sent a heartbeat, by the tracker's clock. None if it never connected.
+    pub async fn time_since_heartbeat(&self) -> Option<Duration> {
+        let received = self.state.lock().await.stats.last_heartbeat_received?;
+        Some(self.clock.now().saturating_duration_since(received))
+    }
+
+    // Time since init by the tracker's clock, None while it isn't initialized
+    pub async fn uptime(&self) -> Option<Duration> {
+        let initialized = self.state.lock().await.stats.initialized_at?;
+        Some(self.clock.now().saturating_duration_since(initialized))
+    }
+
+    // How long the current connection has lasted, starts over on every reconnect. None while not connected.
+    pub async fn connected_duration(&self) -> Option<Duration> {
+        let connected = self.state.lock().await.stats.connected_at?;
+        Some(self.clock.now().saturating_duration_since(connected))
+    }
+
+    // Everything the tracker was configured with, for dumping into logs
+    pub async fn config_snapshot(&self) -> TrackerConfig {
+        let server_addr = self.state.lock().await.discovery_addr;
+        TrackerConfig {
+            mac_address: self.mac_address,
+            firmware_version: self.firmware_version.clone(),
+            board_type: BoardType::from_id(self.board_id),
+            mcu_type: McuType::from_id(self.mcu_id),
+            protocol_mode: self.protocol_mode,
+            feature_flags: self.feature_flags,
+            build_number: self.build_number,
+            server_addr,
+            data_port: self.data_port,
+            server_timeout: Duration::from_millis(self.server_timeout),
+            sensor_count: self.sensors.len(),
+        }
+    }
+
+    pub async fn health_check(&self) -> HealthReport {
+        let state = self.state.lock().await;
+        let connected = state.status == TrackerStatus::Connected;
+        let last_heartbeat_age = state
+            .stats
+            .last_heartbeat_received
+            .map(|received| self.clock.now().saturating_duration_since(received));
+        HealthReport {
+            socket_bound: self.socket.is_some(),
+            connected,
+            last_heartbeat_age,
+            heartbeats_flowing: connected
+                && last_heartbeat_age
+                    .is_some_and(|age| age < Duration::from_millis(self.server_timeout)),
+            sensors_registered: self.sensors.len(),
+            sensors_acknowledged: self
+                .sensors
+                .iter()
+                .filter(|sensor| state.acked_sensors.contains(&sensor.sensor_id))
+                .count(),
+        }
+    }
+
+    // Sequence number of the last packet sent (heartbeats included), 0 if nothing has been sent yet
+    pub fn packet_number(&self) -> u64 {
+        self.send_path.core.lock().unwrap().packet_number()
+    }
+
+    // Makes the next packet go out as `packet_number + 1`, for tests that check exact packet bytes. The server drops
+    // packets numbered lower than ones it has seen, so don't go backwards on a real connection.
+    #[cfg(feature = "testing")]
+    pub fn set_packet_number(&self, packet_number: u64) {
+        self.send_path
+            .core
+            .lock()
+            .unwrap()
+            .set_packet_number(packet_number);
+    }
+
+    pub async fn stats(&self) -> TrackerStats {
+        self.state.lock().await.stats.clone()
+    }
+
+    // Raw datagrams as they went over the wire, oldest first. Empty unless packet_history is set on the builder.
+    pub async fn last_sent(&self) -> Vec<Vec<u8>> {
+        self.state.lock().await.history.sent()
+    }
+
+    // Includes datagrams from servers that were ignored
+    pub async fn last_received(&self) -> Vec<Vec<u8>> {
+        self.state.lock().await.history.received()
     }
 
     /*
@@ -111,186 +790,601 @@ impl EmulatedTracker {
         // Only lock to check/update, then drop before await
         {
             let mut state = self.state.lock().await;
-            if state.status != "initializing" {
+            if !state.status.is_stopped() {
                 return Ok(());
             }
-            self.status_tx.send("idle".to_string()).unwrap();
-            state.status = "idle".to_string();
+            state.set_status(TrackerStatus::Idle, &self.status_tx);
+            state.stats.initialized_at = Some(self.clock.now());
+            // Could be a different server this time
+            state.server_addr = None;
+            state.discovered_servers.clear();
         }
 
-        let bind_address = format!("{}:{}", "0.0.0.0", 0);
-        let socket = UdpSocket::bind(&bind_address)
-            .await
-            .map_err(|e| format!("Failed to bind socket: {}", e))?;
+        // keep_socket_on_deinit leaves the old one in place
+        if self.socket.is_none() {
+            self.socket = Some(Arc::new(self.bind_socket().await?));
+        }
+        if self.separate_data_socket && self.data_socket.is_none() {
+            self.data_socket = Some(Arc::new(self.bind_socket().await?));
+        }
+
+        if self.send_path.dry_run {
+            // Nobody to talk to, so the handshake only gets recorded and the tracker connects to nothing
+            self.send_handshake().await?;
+            let mut state = self.state.lock().await;
+            state.set_status(TrackerStatus::Connected, &self.status_tx);
+            state.stats.connected_at = Some(self.clock.now());
+            drop(state);
+            self.connected_notify.notify_waiters();
+            return Ok(());
+        }
+
+        if self.auto_heartbeat {
+            self.start_heartbeat().await;
+        }
+        if let Some(handler) = self.packet_handler() {
+            self.spawn_task(
+                "receive",
+                handler.run(self.status_rx.clone(), self.recv_buffer_size),
+            );
+        }
+        self.start_watchdog();
+
+        // Handshakes go out with exponential backoff until a server answers, starting over on every init
+        let mut schedule = self.send_path.handshake_schedule();
+        let connected = self.connected();
+        tokio::pin!(connected);
+        loop {
+            self.send_handshake().await?;
+            tokio::select! {
+                _ = &mut connected => break,
+                _ = sleep(schedule.next_delay()) => {}
+            }
+        }
+
+        Ok(())
+    }
 
-        socket
-            .set_broadcast(true)
-            .map_err(|e| format!("Failed to set broadcast option: {}", e))?;
+    // Inits the tracker and keeps it running until `shutdown` resolves (e.g. tokio::signal::ctrl_c()), then
+    // deinits it. Shutting down while still looking for the server works too.
+    pub async fn run_until(&mut self, shutdown: impl Future) -> Result<(), String> {
+        tokio::pin!(shutdown);
+        let init_result = tokio::select! {
+            result = self.init() => Some(result),
+            _ = &mut shutdown => None,
+        };
+        if let Some(result) = init_result {
+            if let Err(e) = result {
+                self.deinit().await?;
+                return Err(e);
+            }
+            shutdown.await;
+        }
+        self.deinit().await
+    }
 
-        self.socket = Some(Arc::new(socket));
+    // Points the tracker at another server, e.g. one found through an mDNS lookup outside this crate, and handshakes
+    // with it right away. A connected tracker drops back to Idle and keeps handshaking with the new server until it
+    // answers, anything the old one still sends is ignored. Handshakes already on their way to the old server
+    // (init or a reconnect) go to the new one from their next attempt. Before init this only changes where init
+    // looks for the server.
+    pub async fn set_server(&self, addr: SocketAddr) -> Result<(), String> {
+        let was_connected = {
+            let mut state = self.state.lock().await;
+            state.discovery_addr = addr;
+            state.publish();
+            if state.status.is_stopped() {
+                return Ok(());
+            }
+            // Stick to the new server before it has answered, the receive task fills in the real address
+            state.server_addr = Some(SocketAddr::new(
+                addr.ip(),
+                self.data_port.unwrap_or(addr.port()),
+            ));
+            // A dry run stays connected, there's no handshake to wait an answer for
+            let was_connected = state.status == TrackerStatus::Connected && !self.send_path.dry_run;
+            if was_connected {
+                state.set_status(TrackerStatus::Idle, &self.status_tx);
+            }
+            was_connected
+        };
 
-        let mut discovery_interval = interval(std::time::Duration::from_secs(1));
-        let server_timeout = self.server_timeout;
+        if was_connected {
+            let _ = self.event_tx.send(TrackerEvent::Disconnected);
+            // Sends the first handshake itself
+            self.refresh_handshake()?;
+            if let Some(socket) = self.socket.as_ref() {
+                self.send_path.start_rediscovery(socket);
+            }
+            Ok(())
+        } else {
+            self.send_handshake().await
+        }
+    }
 
-        self.start_heartbeat().await;
+    // Drops the connection when the server hasn't sent a heartbeat within server_timeout
+    fn start_watchdog(&self) {
+        let mut status_rx = self.status_rx.clone();
+        let status_tx = self.status_tx.clone();
+        let event_tx = self.event_tx.clone();
+        let state = self.state.clone();
+        let clock = self.clock.clone();
+        let server_timeout = Duration::from_millis(self.server_timeout);
+        let check_interval = self.timeout_check_interval;
 
-        // Track last heartbeat time
-        let last_heartbeat = Arc::new(Mutex::new(SystemTime::now()));
-        let last_heartbeat_clone = last_heartbeat.clone();
-        let server_timeout_clone = server_timeout;
-        let state_clone = self.state.clone();
-        tokio::spawn(async move {
+        self.spawn_task("watchdog", async move {
             loop {
-                sleep(Duration::from_millis(server_timeout_clone)).await;
-                let last = last_heartbeat_clone.lock().await;
-                let elapsed = last.elapsed().unwrap_or_default().as_millis() as u64;
-                if elapsed > server_timeout_clone {
-                    println!("Heartbeat timeout detected (no heartbeat within {server_timeout_clone} ms)");
-                    let mut state = state_clone.lock().await;
-                    state.status = "initializing".to_string();
-                    drop(state);
+                tokio::select! {
+                    _ = sleep(check_interval) => {}
+                    changed = status_rx.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                    }
+                }
+                if status_rx.borrow_and_update().is_stopped() {
+                    break;
+                }
+
+                let mut state = state.lock().await;
+                let timed_out = state.status == TrackerStatus::Connected
+                    && state.stats.last_heartbeat_received.is_some_and(|received| {
+                        clock.now().saturating_duration_since(received) > server_timeout
+                    });
+                if timed_out {
+                    warn!(
+                        timeout_ms = server_timeout.as_millis() as u64,
+                        "Heartbeat timeout detected (no heartbeat within {} ms)",
+                        server_timeout.as_millis()
+                    );
+                    state.stats.reconnects += 1;
+                    state.set_status(TrackerStatus::Disconnected, &status_tx);
+                    let _ = event_tx.send(TrackerEvent::Disconnected);
+                    break;
                 }
             }
         });
+    }
 
-        loop {
-            tokio::select! {
-                _ = discovery_interval.tick() => {
-                    let state = self.state.lock().await;
-                    if state.status != "connected-to-server" {
-                        drop(state);
-                        self.send_handshake().await?;
-                    } else {
-                        break;
-                    }
-                }
+    async fn bind_socket(&self) -> Result<UdpSocket, String> {
+        let bind_address = format!("{}:{}", "0.0.0.0", 0);
+        let socket = UdpSocket::bind(&bind_address)
+            .await
+            .map_err(|e| format!("Failed to bind socket: {}", e))?;
 
-                _ = async {
-                    if let Some(socket) = self.socket.as_ref() {
-                        let mut buf = [0u8; 1024];
-                        match socket.recv_from(&mut buf).await {
-                            Ok((size, addr)) => {
-                                if self.debug {
-                                    println!("Received data from: {addr:?}, size: {size}");
-                                    println!("Data: {:?}", String::from_utf8_lossy(&buf[..size]));
-                                }
-                                let mut state = self.state.lock().await;
-                                if state.status != "connected-to-server" {
-                                    state.status = "connected-to-server".to_string();
-                                    self.status_tx.send("connected-to-server".to_string()).unwrap();
-                                }
-                                state.last_received_packet_time = SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_millis() as u16;
-                                drop(state);
-
-                                // Update last heartbeat time if a heartbeat is received
-                                if let Ok((_rest, packet)) = Packet::from_bytes((&buf[..size], 0)) {
-                                    let (_seq, packet_data) = packet.split();
-                                    if let CbPacket::Heartbeat = packet_data {
-                                        let mut last = last_heartbeat.lock().await;
-                                        *last = SystemTime::now();
-                                    }
-                                }
-
-                                if let Err(e) = self.handle_packet(&buf[..size]).await {
-                                    println!("Error handling packet: {e}");
-                                }
-                            }
-                            Err(e) => {
-                                println!("Failed to receive data: {e}");
-                            }
-                        }
-                    }
-                } => {}
+        // Some platforms don't allow it (or not without extra permissions), which only matters when discovering the
+        // server through broadcast
+        if let Err(e) = socket.set_broadcast(true) {
+            if self.require_broadcast {
+                return Err(format!("Failed to set broadcast option: {}", e));
             }
+            warn!(error = %e, "Failed to enable broadcast, only unicast addresses will work");
         }
 
-        Ok(())
+        let sock_ref = socket2::SockRef::from(&socket);
+        if let Some(size) = self.socket_send_buffer {
+            sock_ref
+                .set_send_buffer_size(size)
+                .map_err(|e| format!("Failed to set send buffer size: {}", e))?;
+        }
+        if let Some(size) = self.socket_recv_buffer {
+            sock_ref
+                .set_recv_buffer_size(size)
+                .map_err(|e| format!("Failed to set receive buffer size: {}", e))?;
+        }
+
+        Ok(socket)
     }
 
     pub async fn deinit(&mut self) -> Result<(), String> {
+        // So the last packets still make it out before the socket goes away
+        self.flush().await;
+
         let mut state = self.state.lock().await;
-        if state.status == "initializing" {
+        if state.status == TrackerStatus::Initializing {
             return Ok(());
         }
 
-        self.socket = None;
-        self.status_tx.send("initializing".to_string()).unwrap();
-        state.status = "initializing".to_string();
+        if !self.keep_socket {
+            self.socket = None;
+            self.data_socket = None;
+        }
+        state.set_status(TrackerStatus::Initializing, &self.status_tx);
         drop(state);
         Ok(())
     }
 
-    async fn handle_packet(&self, data: &[u8]) -> Result<(), String> {
-        let (_rest, packet) =
-            Packet::from_bytes((data, 0)).map_err(|e| format!("Failed to parse packet: {e}"))?;
+    // Strict deinit for tests and anything else that wants lifecycle mistakes to show up: fails with NotConnected if
+    // the tracker was never initialized or is already deinitialized, where deinit just returns Ok
+    pub async fn try_deinit(&mut self) -> Result<(), String> {
+        if self.state.lock().await.status == TrackerStatus::Initializing {
+            return Err(TrackerError::NotConnected.into());
+        }
+        self.deinit().await
+    }
 
-        let (_seq, packet_data) = packet.split();
+    // Tears everything down whatever state the tracker is in: stops the background tasks and drops the socket (even
+    // with keep_socket_on_deinit). Unlike deinit, packets still held back by simulated_latency are thrown away.
+    pub async fn force_deinit(&mut self) {
+        #[cfg(feature = "testing")]
+        for handle in std::mem::take(&mut *self.send_path.delayed_sends.lock().unwrap()) {
+            handle.abort();
+        }
+        self.socket = None;
+        self.data_socket = None;
+        self.state
+            .lock()
+            .await
+            .set_status(TrackerStatus::Initializing, &self.status_tx);
+    }
 
-        match packet_data {
-            CbPacket::Heartbeat => {
-                if self.debug {
-                    println!("Received Heartbeat packet");
-                }
-                let packet_data: SbPacket = SbPacket::Heartbeat {};
-                self.send_packet(packet_data).await?
-            }
-            CbPacket::Ping { challenge } => {
-                if self.debug {
-                    println!("Received Ping packet with challenge: {:?}", challenge);
-                }
-                let packet_data: SbPacket = SbPacket::Ping { challenge };
-                self.send_packet(packet_data).await?
-            }
-            CbPacket::Discovery => {
-                // println!("Received Discovery packet");
-            }
-            CbPacket::HandshakeResponse { .. } => {
-                //println!("Received HandshakeResponse packet with version: {}", version);
-            }
-            _ => {
-                println!("Received unknown packet: {:?}", packet_data);
+    // Waits until every packet sent so far is actually on the wire. Sends normally hit the socket before they return,
+    // so this is a no-op unless something holds packets back (only simulated_latency right now).
+    pub async fn flush(&self) {
+        #[cfg(feature = "testing")]
+        {
+            let pending = std::mem::take(&mut *self.send_path.delayed_sends.lock().unwrap());
+            for handle in pending {
+                let _ = handle.await;
             }
         }
+    }
 
-        Ok(())
+    // Runs received bytes through the same handling as the receive task
+    #[cfg(test)]
+    async fn handle_packet(&self, data: &[u8]) -> Result<(), String> {
+        let handler = self.packet_handler().ok_or(TrackerError::NotInitialized)?;
+        handler.handle_packet(data).await
+    }
+
+    fn packet_handler(&self) -> Option<PacketHandler> {
+        Some(PacketHandler {
+            socket: self.socket.as_ref()?.clone(),
+            data_port: self.data_port,
+            server_selection: self.server_selection,
+            auto_heartbeat: self.auto_heartbeat,
+            #[cfg(feature = "testing")]
+            freeze_heartbeat_numbering: self.freeze_heartbeat_numbering,
+            state: self.state.clone(),
+            send_path: self.send_path.clone(),
+            status_tx: self.status_tx.clone(),
+            event_tx: self.event_tx.clone(),
+            incoming_tx: self.incoming_tx.clone(),
+            connected_notify: self.connected_notify.clone(),
+            debug: self.debug.clone(),
+            clock: self.clock.clone(),
+        })
+    }
+
+    // Turns verbose packet logging on or off, takes effect immediately, including for the heartbeat task
+    pub fn set_debug(&self, enabled: bool) {
+        self.debug.store(enabled, Ordering::Relaxed);
+    }
+
+    fn is_debug(&self) -> bool {
+        self.debug.load(Ordering::Relaxed)
     }
 
-    pub fn subscribe_status(&self) -> Receiver<String> {
+    pub fn subscribe_status(&self) -> Receiver<TrackerStatus> {
         self.status_rx.clone()
     }
 
+    // Like subscribe_status, but with the whole state get_state returns. Updated whenever the status changes, a
+    // datagram goes out or one comes in, which is a lot of updates while streaming data, the receiver only ever
+    // sees the newest one though.
+    pub fn subscribe_state(&self) -> Receiver<TrackerState> {
+        self.state_publisher.tx.subscribe()
+    }
+
+    pub async fn is_connected(&self) -> bool {
+        self.state.lock().await.status == TrackerStatus::Connected
+    }
+
+    // Resolves once the tracker is connected to a server, straight away if it already is. The future doesn't borrow
+    // the tracker, so it can be handed to another task before calling init.
+    pub fn connected(&self) -> impl Future<Output = ()> + Send + 'static {
+        let connected_notify = self.connected_notify.clone();
+        let status_rx = self.status_rx.clone();
+        async move {
+            // Register before checking the status so a connection in between isn't missed
+            let notified = connected_notify.notified();
+            if *status_rx.borrow() == TrackerStatus::Connected {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    // Resolves once the tracker stops being connected (the watchdog timing out, reconnect_after_failures kicking in or
+    // deinit), straight away if it isn't connected. Like connected, the future doesn't borrow the tracker.
+    pub fn disconnected(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut status_rx = self.status_rx.clone();
+        async move {
+            // Only fails if the tracker is gone, which is as disconnected as it gets
+            let _ = status_rx
+                .wait_for(|status| *status != TrackerStatus::Connected)
+                .await;
+        }
+    }
+
+    // Only events sent after subscribing are received, a receiver that falls too far behind skips the oldest ones
+    pub fn subscribe_events(&self) -> broadcast::Receiver<TrackerEvent> {
+        self.event_tx.subscribe()
+    }
+
+    // Every packet the server sends that firmware_protocol can parse, after the tracker has handled it. Sensor
+    // acknowledgements and commands aren't CbPackets, those come through is_sensor_acknowledged and
+    // subscribe_events. Only the latest stream gets packets (calling this again ends the previous one), and if it
+    // isn't polled for a while new packets are dropped once INCOMING_CAPACITY are waiting.
+    pub fn incoming(&self) -> impl Stream<Item = CbPacket> + Send + 'static {
+        let (tx, rx) = mpsc::channel(INCOMING_CAPACITY);
+        *self.incoming_tx.lock().unwrap() = Some(tx);
+        futures::stream::unfold(rx, |mut rx| async move {
+            let packet = rx.recv().await?;
+            Some((packet, rx))
+        })
+    }
+
+    // Version the server reported in its handshake response, None until it has answered
+    pub async fn server_info(&self) -> Option<ServerInfo> {
+        self.state.lock().await.server_info.clone()
+    }
+
+    // Every packet sent through the tracker or its simulations (except heartbeats/handshakes) gets written to the
+    // recorder
+    pub fn attach_recorder(&mut self, recorder: Recorder) {
+        *self.send_path.recorder.lock().unwrap() = Some(recorder);
+    }
+
+    pub fn detach_recorder(&mut self) -> Option<Recorder> {
+        self.send_path.recorder.lock().unwrap().take()
+    }
+
     /*
      * Tracker functions
      */
 
+    // Takes the lowest id that isn't in use, see add_sensor_with_id to pick it yourself
     pub async fn add_sensor(
         &mut self,
         sensor_type: ImuType,
         sensor_status: SensorStatus,
     ) -> Result<(), String> {
-        let sensor_id = self.sensors.len() as u8;
-        let sensor = Sensor {
-            sensor_id,
-            sensor_type,
-            sensor_status,
-        };
-        self.send_sensor_info(&sensor).await?;
-        self.sensors.push(sensor);
-        Ok(())
+        let sensor_id = self
+            .free_sensor_ids()
+            .next()
+            .ok_or(TrackerError::TooManySensors)?;
+        self.add_sensor_with_id(sensor_id, sensor_type, sensor_status)
+            .await
+    }
+
+    // For ids that have to stay the same however the sensors get added, they don't need to be contiguous
+    pub async fn add_sensor_with_id(
+        &mut self,
+        sensor_id: u8,
+        sensor_type: ImuType,
+        sensor_status: SensorStatus,
+    ) -> Result<(), String> {
+        if self
+            .sensors
+            .iter()
+            .any(|sensor| sensor.sensor_id == sensor_id)
+        {
+            return Err(TrackerError::DuplicateSensorId(sensor_id).into());
+        }
+        let sensor = Sensor {
+            sensor_id,
+            imu_id: sensor_type.id(),
+            sensor_status,
+        };
+        self.state.lock().await.acked_sensors.remove(&sensor_id);
+        self.send_sensor_info(&sensor).await?;
+        self.sensors.push(sensor);
+        self.refresh_handshake()?;
+        Ok(())
+    }
+
+    // Adds several sensors at once with the lowest free ids, which are returned in the same order. With bundle_packets
+    // on, all the SensorInfo packets go out together in one bundle instead of one datagram each.
+    pub async fn add_sensors(
+        &mut self,
+        specs: impl IntoIterator<Item = (ImuType, SensorStatus)>,
+    ) -> Result<Vec<u8>, String> {
+        let specs: Vec<_> = specs.into_iter().collect();
+        if self.sensors.len() + specs.len() > MAX_SENSORS {
+            return Err(TrackerError::TooManySensors.into());
+        }
+        let sensors: Vec<Sensor> = self
+            .free_sensor_ids()
+            .zip(specs)
+            .map(|(sensor_id, (sensor_type, sensor_status))| Sensor {
+                sensor_id,
+                imu_id: sensor_type.id(),
+                sensor_status,
+            })
+            .collect();
+        {
+            let mut state = self.state.lock().await;
+            for sensor in &sensors {
+                state.acked_sensors.remove(&sensor.sensor_id);
+            }
+        }
+
+        self.send_sensor_infos(&sensors).await?;
+
+        let ids = sensors.iter().map(|sensor| sensor.sensor_id).collect();
+        self.sensors.extend(sensors);
+        self.refresh_handshake()?;
+        Ok(ids)
+    }
+
+    fn free_sensor_ids(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=u8::MAX).filter(|id| !self.sensors.iter().any(|sensor| sensor.sensor_id == *id))
+    }
+
+    // Changes the IMU type of an existing sensor and tells the server, keeping the same id so anything the server
+    // has bound to it stays put
+    pub async fn update_sensor(&mut self, sensor_id: u8, new_type: ImuType) -> Result<(), String> {
+        let idx = self
+            .sensors
+            .iter()
+            .position(|sensor| sensor.sensor_id == sensor_id)
+            .ok_or(TrackerError::UnknownSensor(sensor_id))?;
+        self.sensors[idx].imu_id = new_type.id();
+        self.refresh_handshake()?;
+        self.state.lock().await.acked_sensors.remove(&sensor_id);
+        self.send_sensor_info(&self.sensors[idx]).await
+    }
+
+    // Registers the tracker again from scratch: a new handshake to the discovery address followed by SensorInfo for
+    // every sensor. For when the server restarted and forgot about us without the watchdog noticing.
+    pub async fn resend_handshake(&self) -> Result<(), String> {
+        self.send_handshake().await?;
+        self.announce_sensors().await
+    }
+
+    // Resends SensorInfo for every sensor with its current type and status, without a new handshake. Acknowledgements
+    // start over, so reliable_sensor_info retries these until the server confirms them again.
+    pub async fn announce_sensors(&self) -> Result<(), String> {
+        {
+            let mut state = self.state.lock().await;
+            for sensor in &self.sensors {
+                state.acked_sensors.remove(&sensor.sensor_id);
+            }
+        }
+        self.send_sensor_infos(&self.sensors).await
+    }
+
+    // Ids of the sensors reporting SensorStatus::Ok, in the order they were added
+    pub fn connected_sensor_ids(&self) -> Vec<u8> {
+        self.sensors
+            .iter()
+            .filter(|sensor| sensor.sensor_status == SensorStatus::Ok)
+            .map(|sensor| sensor.sensor_id)
+            .collect()
+    }
+
+    pub async fn is_sensor_acknowledged(&self, sensor_id: u8) -> bool {
+        self.state.lock().await.acked_sensors.contains(&sensor_id)
     }
 
     // TODO: add these to the firmware_protocol package
     // send_battery_level, send_temperature, send_magnetometer_accuracy, send_signal_strength
     async fn send_sensor_info(&self, sensor: &Sensor) -> Result<(), String> {
-        let data = SbPacket::SensorInfo {
-            sensor_id: sensor.sensor_id,
-            sensor_type: self.clone_sensor_type(&sensor.sensor_type),
-            sensor_status: self.clone_sensor_status(&sensor.sensor_status),
+        // owoTrack and legacy trackers have no sensor info, the server just assumes sensor 0
+        if self.single_sensor() {
+            return Ok(());
+        }
+        self.send_packet(sensor.info_packet(), Channel::Control)
+            .await?;
+        self.retry_sensor_info(sensor)
+    }
+
+    // All in one bundle when bundle_packets is on, one datagram each otherwise
+    async fn send_sensor_infos(&self, sensors: &[Sensor]) -> Result<(), String> {
+        if self.use_bundles().await {
+            if sensors.is_empty() {
+                return Ok(());
+            }
+            self.send_bundle(
+                sensors.iter().map(Sensor::info_packet).collect(),
+                Channel::Control,
+            )
+            .await?;
+            for sensor in sensors {
+                self.retry_sensor_info(sensor)?;
+            }
+        } else {
+            for sensor in sensors {
+                self.send_sensor_info(sensor).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Starts the SensorInfo retransmission if reliable_sensor_info is on
+    fn retry_sensor_info(&self, sensor: &Sensor) -> Result<(), String> {
+        if let Some((retry_interval, max_retries)) = self.sensor_info_retry {
+            let bytes = sans_io::encode(0, sensor.info_packet())?;
+            self.retransmit_sensor_info(sensor.sensor_id, bytes, retry_interval, max_retries);
+        }
+        Ok(())
+    }
+
+    // Keeps resending SensorInfo until the server acknowledges it, we run out of retries or get disconnected
+    fn retransmit_sensor_info(
+        &self,
+        sensor_id: u8,
+        bytes: Vec<u8>,
+        retry_interval: Duration,
+        max_retries: u32,
+    ) {
+        // Nothing would ever acknowledge it
+        if self.send_path.dry_run {
+            return;
+        }
+        let socket = match self.socket.as_ref() {
+            Some(s) => s.clone(),
+            None => return,
         };
-        self.send_packet(data).await
+        let state = self.state.clone();
+        let send_path = self.send_path.clone();
+
+        self.spawn_task("sensor_info_retry", async move {
+            for _ in 0..max_retries {
+                sleep(retry_interval).await;
+
+                {
+                    let state_lock = state.lock().await;
+                    if state_lock.acked_sensors.contains(&sensor_id)
+                        || state_lock.status.is_stopped()
+                    {
+                        return;
+                    }
+                }
+                if let Err(e) = send_path.send_renumbered(&socket, &bytes).await {
+                    warn!(sensor_id = sensor_id, error = %e, "Failed to resend SensorInfo");
+                }
+            }
+
+            if !state.lock().await.acked_sensors.contains(&sensor_id) {
+                warn!(
+                    sensor_id = sensor_id,
+                    retries = max_retries,
+                    "Server never acknowledged SensorInfo"
+                );
+            }
+        });
+    }
+
+    // Stops rotation/acceleration data (corrections and send_rotation_all included) without disconnecting, like the
+    // tracker sitting still. Heartbeats, pings and everything else keep going so the server doesn't time us out.
+    // Data sent while paused is dropped, not queued, and the send returns the sequence number of the last packet
+    // that went out.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    // Last normal rotation that went out for the sensor (corrections don't count), e.g. to send the last pose again
+    // after resume. None until one has been sent, paused and throttled sends don't change it.
+    pub fn last_rotation(&self, sensor_id: u8) -> Option<SlimeQuaternion> {
+        self.last_rotations
+            .lock()
+            .unwrap()
+            .get(&sensor_id)
+            .map(|(quat, _)| (*quat).into())
+    }
+
+    fn paused_seq(&self) -> Option<u64> {
+        self.is_paused().then(|| self.packet_number())
     }
 
     pub async fn send_rotation(
@@ -299,67 +1393,445 @@ impl EmulatedTracker {
         data_type: SensorDataType,
         rotation_data: SlimeQuaternion,
         accuracy: u8,
-    ) -> Result<(), String> {
-        let data = SbPacket::RotationData {
-            sensor_id,
-            data_type,
-            quat: rotation_data,
-            calibration_info: accuracy,
+    ) -> Result<u64, String> {
+        let kind = DataKind::of(&data_type);
+        self.check_rotation(sensor_id, kind)?;
+        if let Some(seq) = self.paused_seq() {
+            return Ok(seq);
+        }
+        self.throttle(&[(sensor_id, kind)]).await?;
+        self.send_rotation_now(sensor_id, data_type, rotation_data, accuracy)
+            .await
+    }
+
+    fn check_rotation(&self, sensor_id: u8, kind: DataKind) -> Result<(), String> {
+        if self.single_sensor() && sensor_id != 0 {
+            return Err(format!(
+                "{} trackers only have sensor 0, can't send rotation for sensor {sensor_id}",
+                self.flavour()
+            ));
+        }
+        if self.is_legacy() && kind == DataKind::Correction {
+            return Err("Legacy trackers can't send correction rotation".to_string());
+        }
+        Ok(())
+    }
+
+    // send_rotation past the pause and throttle checks
+    async fn send_rotation_now(
+        &self,
+        sensor_id: u8,
+        data_type: SensorDataType,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+    ) -> Result<u64, String> {
+        let kind = DataKind::of(&data_type);
+        let quat = Quat::from(&rotation_data);
+        self.check_rotation_jump(sensor_id, kind, quat)?;
+        let seq = match self.protocol_mode {
+            ProtocolMode::SlimeVr if !self.is_legacy() => {
+                let data = SbPacket::RotationData {
+                    sensor_id,
+                    data_type,
+                    quat: rotation_data,
+                    calibration_info: accuracy,
+                };
+                self.send_packet(data, Channel::Data).await?
+            }
+            _ => {
+                self.send_raw_packet(RawPacket::legacy_rotation(&rotation_data), Channel::Data)
+                    .await?
+            }
         };
-        self.send_packet(data).await
+        self.mark_data_sent(sensor_id, kind);
+        self.record_rotation(sensor_id, kind, quat);
+        Ok(seq)
     }
 
-    pub async fn send_acceleration(
+    // Sends the same rotation for every sensor, in one bundle when bundle_packets is on (or a few, see
+    // max_datagram_size). Returns the sequence number for each sensor in the order they were added, the same for
+    // sensors that shared a bundle. With data_rate_hz this is throttled as a whole, see throttle.
+    pub async fn send_rotation_all(
+        &self,
+        data_type: SensorDataType,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+    ) -> Result<Vec<u64>, String> {
+        let kind = DataKind::of(&data_type);
+        let quat = Quat::from(rotation_data);
+        if let Some(seq) = self.paused_seq() {
+            return Ok(vec![seq; self.sensors.len()]);
+        }
+        let keys: Vec<_> = self
+            .sensors
+            .iter()
+            .map(|sensor| (sensor.sensor_id, kind))
+            .collect();
+        self.throttle(&keys).await?;
+
+        if !self.use_bundles().await {
+            let mut seqs = Vec::with_capacity(self.sensors.len());
+            for sensor in &self.sensors {
+                self.check_rotation(sensor.sensor_id, kind)?;
+                seqs.push(
+                    self.send_rotation_now(
+                        sensor.sensor_id,
+                        kind.data_type(),
+                        quat.into(),
+                        accuracy,
+                    )
+                    .await?,
+                );
+            }
+            return Ok(seqs);
+        }
+
+        let mut packets = Vec::with_capacity(self.sensors.len());
+        for sensor in &self.sensors {
+            self.check_rotation_jump(sensor.sensor_id, kind, quat)?;
+            packets.push(SbPacket::RotationData {
+                sensor_id: sensor.sensor_id,
+                data_type: kind.data_type(),
+                quat: quat.into(),
+                calibration_info: accuracy,
+            });
+        }
+        if packets.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // One for every packet, so one for every sensor
+        let seqs = self.send_bundle(packets, Channel::Data).await?;
+        for sensor in &self.sensors {
+            self.mark_data_sent(sensor.sensor_id, kind);
+            self.record_rotation(sensor.sensor_id, kind, quat);
+        }
+        Ok(seqs)
+    }
+
+    // Normal rotation is the orientation the server shows for the tracker, and is what should be streamed.
+    // Correction rotation is an occasional absolute reference (e.g. magnetometer heading) that a server may use to
+    // correct drift in the normal stream, it isn't displayed and servers without drift correction ignore it.
+    pub async fn send_correction(
         &self,
         sensor_id: u8,
-        acceleration: (f32, f32, f32),
-    ) -> Result<(), String> {
-        let data = SbPacket::Acceleration {
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+    ) -> Result<u64, String> {
+        self.send_rotation(
             sensor_id,
-            vector: acceleration,
+            SensorDataType::Correction,
+            rotation_data,
+            accuracy,
+        )
+        .await
+    }
+
+    pub async fn send_acceleration(
+        &self,
+        sensor_id: u8,
+        acceleration: impl Into<Vec3>,
+    ) -> Result<u64, String> {
+        if let Some(seq) = self.paused_seq() {
+            return Ok(seq);
+        }
+        if self.is_legacy() && sensor_id != 0 {
+            return Err(format!(
+                "Legacy trackers only have sensor 0, can't send acceleration for sensor {sensor_id}"
+            ));
+        }
+        self.throttle(&[(sensor_id, DataKind::Acceleration)])
+            .await?;
+        self.send_acceleration_now(sensor_id, acceleration.into())
+            .await
+    }
+
+    // send_acceleration past the pause and throttle checks
+    async fn send_acceleration_now(
+        &self,
+        sensor_id: u8,
+        acceleration: Vec3,
+    ) -> Result<u64, String> {
+        let seq = if self.is_legacy() {
+            self.send_raw_packet(RawPacket::legacy_acceleration(acceleration), Channel::Data)
+                .await?
+        } else {
+            let data = SbPacket::Acceleration {
+                sensor_id,
+                vector: acceleration.into(),
+            };
+            self.send_packet(data, Channel::Data).await?
         };
-        self.send_packet(data).await
+        self.mark_data_sent(sensor_id, DataKind::Acceleration);
+        Ok(seq)
     }
 
-    pub async fn send_battery_level(&self, percentage: f32, voltage: f32) -> Result<(), String> {
-        let data = SbPacket::Battery {
-            percentage,
-            voltage,
+    // Like send_acceleration, but `frame` says which frame the acceleration is in. World needs a rotation to have been
+    // sent for the sensor first.
+    pub async fn send_acceleration_in(
+        &self,
+        sensor_id: u8,
+        acceleration: impl Into<Vec3>,
+        frame: AccelFrame,
+    ) -> Result<u64, String> {
+        let acceleration = match frame {
+            AccelFrame::SensorLocal => acceleration.into(),
+            AccelFrame::World => {
+                let rotation = self
+                    .last_rotations
+                    .lock()
+                    .unwrap()
+                    .get(&sensor_id)
+                    .map(|(quat, _)| *quat)
+                    .ok_or_else(|| {
+                        format!("No rotation sent for sensor {sensor_id} yet, can't convert from world frame")
+                    })?;
+                rotation.conjugate().rotate(acceleration.into())
+            }
         };
-        self.send_packet(data).await
+        self.send_acceleration(sensor_id, acceleration).await
+    }
+
+    // Rotation and acceleration of one sensor for the same moment, the usual per-sensor step of an emulation loop.
+    // With bundle_packets on (and a server that takes bundles) both go out in one datagram, rotation first, so the
+    // server never sees the acceleration before the rotation it goes with. Otherwise it's send_rotation followed by
+    // send_acceleration. Returns the sequence numbers of the rotation and the acceleration, which are the same when
+    // bundled. Pause and rotation_jump_guard apply to each as if they were sent separately, throttling to the pair
+    // as a whole (see throttle).
+    pub async fn send_imu_frame(
+        &self,
+        sensor_id: u8,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+        acceleration: impl Into<Vec3>,
+    ) -> Result<(u64, u64), String> {
+        if let Some(seq) = self.paused_seq() {
+            return Ok((seq, seq));
+        }
+        self.check_rotation(sensor_id, DataKind::Rotation)?;
+        if self.is_legacy() && sensor_id != 0 {
+            return Err(format!(
+                "Legacy trackers only have sensor 0, can't send acceleration for sensor {sensor_id}"
+            ));
+        }
+        self.throttle(&[
+            (sensor_id, DataKind::Rotation),
+            (sensor_id, DataKind::Acceleration),
+        ])
+        .await?;
+        let acceleration = acceleration.into();
+
+        if !self.use_bundles().await {
+            let rotation_seq = self
+                .send_rotation_now(sensor_id, SensorDataType::Normal, rotation_data, accuracy)
+                .await?;
+            let acceleration_seq = self.send_acceleration_now(sensor_id, acceleration).await?;
+            return Ok((rotation_seq, acceleration_seq));
+        }
+
+        let quat = Quat::from(&rotation_data);
+        self.check_rotation_jump(sensor_id, DataKind::Rotation, quat)?;
+        let packets = vec![
+            SbPacket::RotationData {
+                sensor_id,
+                data_type: SensorDataType::Normal,
+                quat: rotation_data,
+                calibration_info: accuracy,
+            },
+            SbPacket::Acceleration {
+                sensor_id,
+                vector: acceleration.into(),
+            },
+        ];
+        // Only split with a tiny max_datagram_size
+        let seqs = self.send_bundle(packets, Channel::Data).await?;
+        self.mark_data_sent(sensor_id, DataKind::Rotation);
+        self.mark_data_sent(sensor_id, DataKind::Acceleration);
+        self.record_rotation(sensor_id, DataKind::Rotation, quat);
+        Ok((seqs[0], seqs[1]))
+    }
+
+    // The protocol has no timestamp field (the server goes by arrival time), so these hold the packet back
+    // until `at` instead. Useful for keeping the relative timing of recorded motion.
+    pub async fn send_rotation_at(
+        &self,
+        sensor_id: u8,
+        data_type: SensorDataType,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+        at: Instant,
+    ) -> Result<u64, String> {
+        sleep_until(at.into()).await;
+        self.send_rotation(sensor_id, data_type, rotation_data, accuracy)
+            .await
+    }
+
+    pub async fn send_acceleration_at(
+        &self,
+        sensor_id: u8,
+        acceleration: impl Into<Vec3>,
+        at: Instant,
+    ) -> Result<u64, String> {
+        sleep_until(at.into()).await;
+        self.send_acceleration(sensor_id, acceleration).await
+    }
+
+    // Battery is per tracker, the packet has no sensor id since all the sensors run off the same battery.
+    // `percentage` is 0-100 and gets clamped to that, `voltage` is in volts and has to be within MAX_BATTERY_VOLTAGE.
+    // Either one being NaN/infinite is an error.
+    pub async fn send_battery_level(&self, percentage: f32, voltage: f32) -> Result<u64, String> {
+        let data = Self::battery_packet(percentage, voltage)?;
+        self.send_packet(data, Channel::Control).await
+    }
+
+    // Checks for send_battery_level, shared with the battery simulation
+    pub(crate) fn battery_packet(percentage: f32, voltage: f32) -> Result<SbPacket, TrackerError> {
+        if !percentage.is_finite() || !(0.0..=MAX_BATTERY_VOLTAGE).contains(&voltage) {
+            return Err(TrackerError::InvalidBattery {
+                percentage,
+                voltage,
+            });
+        }
+        Ok(SbPacket::Battery {
+            percentage: percentage.clamp(0.0, 100.0),
+            voltage,
+        })
     }
 
-    pub async fn send_temperature(&self, sensor_id: u8, temperature: f32) -> Result<(), String> {
+    // Temperature is per sensor (it's read from the IMU), in °C. The sensor has to have been added.
+    pub async fn send_temperature(&self, sensor_id: u8, temperature: f32) -> Result<u64, String> {
+        if !self
+            .sensors
+            .iter()
+            .any(|sensor| sensor.sensor_id == sensor_id)
+        {
+            return Err(TrackerError::UnknownSensor(sensor_id).into());
+        }
         let data = SbPacket::Temperature {
             sensor_id,
             temperature,
         };
-        self.send_packet(data).await
+        self.send_packet(data, Channel::Control).await
     }
 
-    pub async fn send_signal_strength(&self, sensor_id: u8, strength: i8) -> Result<(), String> {
+    pub async fn send_signal_strength(&self, sensor_id: u8, strength: i8) -> Result<u64, String> {
         let data = SbPacket::SignalStrength {
             sensor_id,
             strength,
         };
-        self.send_packet(data).await
+        self.send_packet(data, Channel::Control).await
     }
 
     pub async fn send_magnetometer_accuracy(
         &self,
         sensor_id: u8,
         accuracy: f32,
-    ) -> Result<(), String> {
+    ) -> Result<u64, String> {
         let data = SbPacket::MagAccuracy {
             sensor_id,
             accuracy,
         };
-        self.send_packet(data).await
+        self.send_packet(data, Channel::Control).await
+    }
+
+    // Value of a flex (resistance) sensor, like the ones on gloves
+    pub async fn send_flex_data(&self, sensor_id: u8, value: f32) -> Result<u64, String> {
+        self.require_modern("Flex data").await?;
+        self.send_raw_packet(RawPacket::flex_data(sensor_id, value), Channel::Control)
+            .await
+    }
+
+    // Position in metres for trackers that know where they are (optical/lighthouse) rather than just their rotation,
+    // goes with FirmwareFeatureFlags::PositionData. There's no official packet for it, so this only works once the
+    // builder's position_packet_type says which one the server expects.
+    pub async fn send_position(
+        &self,
+        sensor_id: u8,
+        position: impl Into<Vec3>,
+    ) -> Result<u64, String> {
+        let packet_type = self.position_packet_type.ok_or(
+            "Position data has no packet type in the protocol, set one with position_packet_type",
+        )?;
+        self.require_modern("Position data").await?;
+        let packet = RawPacket::position(packet_type, sensor_id, position);
+        self.send_raw_packet(packet, Channel::Control).await
     }
 
-    pub async fn send_user_action(&self, action: ActionType) -> Result<(), String> {
+    pub async fn send_user_action(&self, action: ActionType) -> Result<u64, String> {
         let data = SbPacket::UserAction { action };
-        self.send_packet(data).await
+        self.send_packet(data, Channel::Control).await
+    }
+
+    // Sends a ping with a random challenge, the round trip is recorded in stats once the server echoes it. Like
+    // every send, returns the sequence number the packet went out with.
+    pub async fn send_ping(&self) -> Result<u64, String> {
+        let challenge: [u8; 4] = rand::random();
+        {
+            let mut state = self.state.lock().await;
+            state.stats.pending_ping = Some((challenge, self.clock.now()));
+        }
+        let data = SbPacket::Ping { challenge };
+        self.send_packet(data, Channel::Control).await
+    }
+
+    // One heartbeat right now, on top of (or with auto_heartbeat off, instead of) the automatic ones
+    pub async fn send_heartbeat(&self) -> Result<u64, String> {
+        let seq = self
+            .send_packet(SbPacket::Heartbeat, Channel::Control)
+            .await?;
+        self.state.lock().await.stats.last_heartbeat_sent = Some(self.clock.now());
+        Ok(seq)
+    }
+
+    // Sends a single packet somewhere other than the server (e.g. a packet capture tool), numbered like any other
+    // packet so it still fits in the sequence the server sees
+    pub async fn send_packet_to(&self, data: SbPacket, addr: SocketAddr) -> Result<u64, String> {
+        let socket = self.socket.as_ref().ok_or(TrackerError::NotInitialized)?;
+        // Formatted up front, encoding takes the packet
+        let packet = self.is_debug().then(|| format!("{data:?}"));
+        let (packet_number, bytes) = self.build_packet(|core| core.packet(data))?;
+
+        if let Some(packet) = packet {
+            trace!(
+                seq = packet_number,
+                packet = %packet,
+                peer = %addr,
+                bytes = %HexDump(&bytes),
+                "Sending packet"
+            );
+        }
+
+        self.send_path
+            .send_to(socket, addr, packet_number, &bytes)
+            .await?;
+        Ok(packet_number)
+    }
+
+    // Writes `bytes` to the server exactly as given, for fuzzing/testing how robust the server is. Nothing is
+    // checked: no header, no sequence number, and the packet doesn't even have to be valid. None of the send
+    // options (simulated loss/latency, reconnect_after_failures, the recorder) apply either.
+    pub async fn send_raw(&self, bytes: &[u8]) -> Result<(), String> {
+        let socket = self.socket.as_ref().ok_or(TrackerError::NotInitialized)?;
+        let target = self.data_target().await;
+
+        if self.is_debug() {
+            trace!(
+                size = bytes.len(),
+                peer = %target,
+                bytes = %HexDump(bytes),
+                "Sending raw bytes"
+            );
+        }
+
+        if !self.send_path.dry_run {
+            socket
+                .send_to(bytes, target)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        self.record_sent(bytes).await;
+        Ok(())
     }
 
     /*
@@ -370,45 +1842,47 @@ impl EmulatedTracker {
         let socket = match self.socket.as_ref() {
             Some(s) => s.clone(),
             None => {
-                println!("Socket not initialized, cannot start heartbeat");
+                warn!("Socket not initialized, cannot start heartbeat");
                 return;
             }
         };
         let status_rx = self.status_rx.clone();
-        let server_ip = self.server_ip.clone();
-        let server_port = self.server_port;
         let state = self.state.clone();
-        let debug = self.debug;
+        let send_path = self.send_path.clone();
+        let debug = self.debug.clone();
+        let clock = self.clock.clone();
+        #[cfg(feature = "testing")]
+        let frozen = self.freeze_heartbeat_numbering;
+        #[cfg(not(feature = "testing"))]
+        let frozen = false;
 
-        tokio::spawn(async move {
+        self.spawn_task("heartbeat", async move {
             let result: Result<(), String> = async {
                 loop {
-                    if status_rx.borrow().as_str() == "initializing" {
+                    if status_rx.borrow().is_stopped() {
                         break;
                     }
 
                     // gotta manually grab these info instead of using my methods cause self has a limited lifetime
                     // whatever that means man (i kinda get it but not really)
-                    let packet_number = {
-                        let mut state_lock = state.lock().await;
-                        state_lock.packet_number += 1;
-                        state_lock.packet_number
-                    };
-                    let packet = Packet::new(packet_number, SbPacket::Heartbeat);
+                    let target = state.lock().await.data_target();
 
                     // send heartbeat
-                    if let Err(e) = socket
-                        .send_to(
-                            &packet.to_bytes().unwrap(),
-                            (server_ip.as_str(), server_port),
-                        )
-                        .await
-                    {
-                        println!("Failed to send heartbeat packet: {e}");
-                    }
-
-                    if debug {
-                        println!("Sending packet: {:?}", packet);
+                    match send_path.send_heartbeat(&socket, frozen).await {
+                        Ok(packet_number) => {
+                            state.lock().await.stats.last_heartbeat_sent = Some(clock.now());
+                            if debug.load(Ordering::Relaxed) {
+                                trace!(
+                                    seq = packet_number,
+                                    packet_type = "Heartbeat",
+                                    peer = %target,
+                                    "Sending packet"
+                                );
+                            }
+                        }
+                        Err(e) => {
+                            warn!(error = %e, "Failed to send heartbeat packet");
+                        }
                     }
 
                     sleep(std::time::Duration::from_secs(1)).await;
@@ -417,155 +1891,2308 @@ impl EmulatedTracker {
             }
             .await;
             if let Err(e) = result {
-                println!("Error in heartbeat task: {}", e);
+                error!(error = %e, "Error in heartbeat task");
             }
         });
     }
 
-    async fn send_packet(&self, data: SbPacket) -> Result<(), String> {
-        let packet_number = self.get_packet_number().await?;
-        let packet = Packet::new(packet_number, data);
+    async fn send_packet(&self, data: SbPacket, channel: Channel) -> Result<u64, String> {
+        // Formatted up front, encoding takes the packet
+        let packet = self.is_debug().then(|| format!("{data:?}"));
+        let (packet_number, bytes) = self.build_packet(|core| core.packet(data))?;
 
-        if self.debug {
-            println!("Sending packet: {:?}", packet);
+        if let Some(packet) = packet {
+            trace!(
+                seq = packet_number,
+                packet = %packet,
+                bytes = %HexDump(&bytes),
+                "Sending packet"
+            );
         }
 
-        let socket = self.socket.as_ref().expect("Socket not initialized");
-        socket
-            .send_to(
-                &packet.to_bytes().unwrap(),
-                (self.server_ip.clone(), self.server_port),
-            )
-            .await
-            .map_err(|e| e.to_string())?;
+        self.send_bytes(packet_number, &bytes, channel).await?;
+        Ok(packet_number)
+    }
+
+    // The server has to support bundles (bundle_packets is off by default). Packets that don't fit in one
+    // max_datagram_size datagram go out in several bundles, so this returns the sequence number of the bundle each
+    // packet went out in, in the same order.
+    async fn send_bundle(
+        &self,
+        packets: Vec<SbPacket>,
+        channel: Channel,
+    ) -> Result<Vec<u64>, String> {
+        let mut seqs = Vec::with_capacity(packets.len());
+        for (bundle, count) in RawPacket::bundles(packets, self.max_datagram_size)? {
+            let seq = self.send_raw_packet(bundle, channel).await?;
+            seqs.resize(seqs.len() + count, seq);
+        }
+        Ok(seqs)
+    }
+
+    // For packets firmware_protocol doesn't have yet
+    async fn send_raw_packet(&self, packet: RawPacket, channel: Channel) -> Result<u64, String> {
+        let (packet_number, bytes) = self.build_packet(|core| Ok(core.raw_packet(&packet)))?;
+
+        if self.is_debug() {
+            trace!(
+                seq = packet_number,
+                packet_type = packet.packet_type,
+                bytes = %HexDump(&bytes),
+                "Sending packet"
+            );
+        }
+
+        self.send_bytes(packet_number, &bytes, channel).await?;
+        Ok(packet_number)
+    }
 
+    async fn send_bytes(
+        &self,
+        packet_number: u64,
+        bytes: &[u8],
+        channel: Channel,
+    ) -> Result<(), String> {
+        let socket = match channel {
+            Channel::Data => self.data_socket.as_ref().or(self.socket.as_ref()),
+            Channel::Control => self.socket.as_ref(),
+        }
+        .ok_or(TrackerError::NotInitialized)?;
+        self.send_path.send(socket, packet_number, bytes).await?;
+        self.send_path.record(packet_number, bytes);
         Ok(())
     }
 
+    // Sends already serialized packet bytes with the sequence number swapped for a fresh one
+    pub(crate) async fn send_renumbered(&self, bytes: &[u8]) -> Result<(), String> {
+        let (packet_number, bytes) = self.build_packet(|core| core.renumber(bytes))?;
+        let packet_type = u32::from_be_bytes(bytes[..4].try_into().unwrap());
+        self.send_bytes(packet_number, &bytes, Channel::of_packet_type(packet_type))
+            .await
+    }
+
+    // The handshake doubles as the tracker's announce: the protocol has no separate discovery packet going from the
+    // tracker to the server (type 0 that way is the heartbeat), so this is what discovery sends
     async fn send_handshake(&self) -> Result<(), String> {
-        let data = SbPacket::Handshake {
-            board: self.clone_board_type(),
-            imu: self.clone_sensor_type(&ImuType::Unknown(0)),
-            mcu: self.clone_mcu_type(),
-            imu_info: (0, 0, 0),
-            build: 13, // current version is 13 apparently
-            firmware: self.firmware_version.clone().into(),
-            mac_address: self.mac_address,
+        let socket = self.socket.as_ref().ok_or(TrackerError::NotInitialized)?;
+        self.refresh_handshake()?;
+        self.send_path.send_handshake(socket).await
+    }
+
+    // What the handshake announces, it only changes with the sensors
+    fn handshake(&self) -> Handshake {
+        let imu = match (self.primary_imu, self.sensors.first()) {
+            (Some(imu), _) => imu,
+            (None, Some(sensor)) => sensor.imu_id,
+            (None, None) => 0,
         };
-        let packet = Packet::new(0, data);
+        Handshake {
+            board: BoardType::from_id(self.board_id),
+            imu: ImuType::from_id(imu),
+            mcu: McuType::from_id(self.mcu_id),
+            imu_info: self.imu_info,
+            build: self.build_number,
+            firmware: self.firmware_version.clone(),
+            mac_address: self.mac_address,
+            legacy: self.is_legacy(),
+        }
+    }
 
-        let socket = self.socket.as_ref().ok_or("Socket not initialized")?;
-        socket
-            .send_to(
-                &packet.to_bytes().unwrap(),
-                (self.server_ip.clone(), self.server_port),
-            )
-            .await
-            .map_err(|e| e.to_string())?;
+    // Hands the core the latest handshake, for rediscovery started from a background task, which has no way of
+    // building one itself. Serialized here first so a handshake that can't be sent fails the caller instead.
+    fn refresh_handshake(&self) -> Result<(), String> {
+        let handshake = self.handshake();
+        handshake.to_bytes()?;
+        self.send_path.core.lock().unwrap().set_handshake(handshake);
         Ok(())
     }
 
-    async fn get_packet_number(&self) -> Result<u64, String> {
-        let mut state = self.state.lock().await;
-        state.packet_number += 1;
-        Ok(state.packet_number)
-    }
-
-    fn clone_board_type(&self) -> BoardType {
-        match &self.board_type {
-            BoardType::SlimeVRLegacy => BoardType::SlimeVRLegacy,
-            BoardType::SlimeVRDev => BoardType::SlimeVRDev,
-            BoardType::NodeMCU => BoardType::NodeMCU,
-            BoardType::Custom => BoardType::Custom,
-            BoardType::WRoom32 => BoardType::WRoom32,
-            BoardType::WemosD1Mini => BoardType::WemosD1Mini,
-            BoardType::TTGOTBase => BoardType::TTGOTBase,
-            BoardType::ESP01 => BoardType::ESP01,
-            BoardType::SlimeVR => BoardType::SlimeVR,
-            BoardType::LolinC3Mini => BoardType::LolinC3Mini,
-            BoardType::Beetle32C3 => BoardType::Beetle32C3,
-            BoardType::ESP32C3DevKitM1 => BoardType::ESP32C3DevKitM1,
-            BoardType::OwoTrack => BoardType::OwoTrack,
-            BoardType::Wrangler => BoardType::Wrangler,
-            BoardType::Mocopi => BoardType::Mocopi,
-            BoardType::WemosWroom02 => BoardType::WemosWroom02,
-            BoardType::XiaoEsp32C3 => BoardType::XiaoEsp32C3,
-            BoardType::Haritora => BoardType::Haritora,
-            BoardType::ESP32C6DevKitC1 => BoardType::ESP32C6DevKitC1,
-            BoardType::GloveImuSlimeVRDev => BoardType::GloveImuSlimeVRDev,
-            BoardType::Gestures => BoardType::Gestures,
-            BoardType::DevReserved => BoardType::DevReserved,
-            BoardType::Unknown(val) => BoardType::Unknown(*val),
-            _ => BoardType::Unknown(0),
-        }
-    }
-
-    fn clone_mcu_type(&self) -> McuType {
-        match &self.mcu_type {
-            McuType::Esp8266 => McuType::Esp8266,
-            McuType::Esp32 => McuType::Esp32,
-            McuType::OwoTrackAndroid => McuType::OwoTrackAndroid,
-            McuType::Wrangler => McuType::Wrangler,
-            McuType::OwoTrackIos => McuType::OwoTrackIos,
-            McuType::Esp32C3 => McuType::Esp32C3,
-            McuType::Mocopi => McuType::Mocopi,
-            McuType::Haritora => McuType::Haritora,
-            McuType::DevReserved => McuType::DevReserved,
-            McuType::Unknown(val) => McuType::Unknown(*val),
-            _ => McuType::Unknown(0),
-        }
-    }
-
-    fn clone_sensor_type(&self, imu_type: &ImuType) -> ImuType {
-        match imu_type {
-            ImuType::Mpu9250 => ImuType::Mpu9250,
-            ImuType::Mpu6500 => ImuType::Mpu6500,
-            ImuType::Bno080 => ImuType::Bno080,
-            ImuType::Bno085 => ImuType::Bno085,
-            ImuType::Bno055 => ImuType::Bno055,
-            ImuType::Mpu6050 => ImuType::Mpu6050,
-            ImuType::Bno086 => ImuType::Bno086,
-            ImuType::Bmi160 => ImuType::Bmi160,
-            ImuType::Icm20948 => ImuType::Icm20948,
-            ImuType::Icm42688 => ImuType::Icm42688,
-            ImuType::Bmi270 => ImuType::Bmi270,
-            ImuType::Lsm6ds3trc => ImuType::Lsm6ds3trc,
-            ImuType::Lsm6dsv => ImuType::Lsm6dsv,
-            ImuType::Lsm6dso => ImuType::Lsm6dso,
-            ImuType::Lsm6dsr => ImuType::Lsm6dsr,
-            ImuType::Icm45686 => ImuType::Icm45686,
-            ImuType::Icm45605 => ImuType::Icm45605,
-            ImuType::AdcResistance => ImuType::AdcResistance,
-            ImuType::DevReserved => ImuType::DevReserved,
-            ImuType::Unknown(val) => ImuType::Unknown(*val),
-            _ => ImuType::Unknown(0),
-        }
-    }
-
-    fn clone_sensor_status(&self, status: &SensorStatus) -> SensorStatus {
-        match status {
-            SensorStatus::Ok => SensorStatus::Ok,
-            SensorStatus::Offline => SensorStatus::Offline,
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[tokio::test]
-    async fn test_all() {
-        use {sleep, Duration};
+    fn spawn_task<F>(&self, name: &'static str, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        spawn_task(self.mac_address, name, future)
+    }
 
-        let mac_address = [0xDE, 0xAD, 0xBE, 0xEF, 0x01, 0x02];
-        let firmware_version = "tracker_emulation-rs test".to_string();
+    // Data rate limit: an update that comes in before the sensor's next slot is dropped rather than queued or sent
+    // later, since a newer one will follow anyway. The trade-off is that the newest value only reaches the server if
+    // another update comes after the slot opens, so a stream that stops should send its final value again once the
+    // interval is up. Dropped sends return TrackerError::Throttled, as there's no sequence number for them.
+    // Several keys (send_rotation_all, send_imu_frame) only count as throttled when all of them are, otherwise
+    // everything goes out together.
+    async fn throttle(&self, keys: &[(u8, DataKind)]) -> Result<(), String> {
+        let Some(data_interval) = self.data_interval else {
+            return Ok(());
+        };
+        let now = self.clock.now();
+        let throttled = {
+            let last_data_sends = self.last_data_sends.lock().unwrap();
+            !keys.is_empty()
+                && keys.iter().all(|key| {
+                    last_data_sends.get(key).is_some_and(|sent_at| {
+                        now.saturating_duration_since(*sent_at) < data_interval
+                    })
+                })
+        };
+        if !throttled {
+            return Ok(());
+        }
+        self.state.lock().await.stats.throttled_packets += keys.len() as u64;
+        Err(TrackerError::Throttled(keys[0].0).into())
+    }
 
-        // Create tracker instance
-        let mut tracker =
-            EmulatedTracker::new(mac_address, firmware_version, None, None, None, None, None)
-                .await
-                .expect("Failed to create EmulatedTracker");
+    // See EmulatedTrackerBuilder::rotation_jump_guard
+    fn check_rotation_jump(&self, sensor_id: u8, kind: DataKind, quat: Quat) -> Result<(), String> {
+        let Some((max_speed, action)) = self.rotation_guard else {
+            return Ok(());
+        };
+        if kind != DataKind::Rotation {
+            return Ok(());
+        }
+        let Some((last, sent_at)) = self.last_rotations.lock().unwrap().get(&sensor_id).copied()
+        else {
+            return Ok(());
+        };
+        // Back to back sends still count as a millisecond apart, otherwise any change at all would be a jump
+        let elapsed = sent_at
+            .elapsed()
+            .max(Duration::from_millis(1))
+            .as_secs_f32();
+        let angle = last.angle_to(quat);
+        if angle / elapsed <= max_speed {
+            return Ok(());
+        }
+
+        let degrees = angle.to_degrees();
+        match action {
+            JumpAction::Warn => {
+                warn!(
+                    sensor_id = sensor_id,
+                    degrees = degrees,
+                    elapsed_ms = elapsed * 1000.0,
+                    "Implausible rotation jump"
+                );
+                Ok(())
+            }
+            JumpAction::Reject => Err(format!(
+                "Rotation for sensor {sensor_id} jumped {degrees:.1} degrees in {:.1} ms",
+                elapsed * 1000.0
+            )),
+        }
+    }
+
+    fn record_rotation(&self, sensor_id: u8, kind: DataKind, quat: Quat) {
+        if kind == DataKind::Rotation {
+            self.last_rotations
+                .lock()
+                .unwrap()
+                .insert(sensor_id, (quat, Instant::now()));
+        }
+    }
+
+    fn mark_data_sent(&self, sensor_id: u8, kind: DataKind) {
+        if self.data_interval.is_some() {
+            self.last_data_sends
+                .lock()
+                .unwrap()
+                .insert((sensor_id, kind), self.clock.now());
+        }
+    }
+
+    // Whether the server gets the newer packet formats, see PacketNegotiation
+    pub async fn uses_modern_packets(&self) -> bool {
+        if self.is_legacy() {
+            return false;
+        }
+        match self.packet_negotiation {
+            PacketNegotiation::Modern => true,
+            PacketNegotiation::Legacy => false,
+            PacketNegotiation::MinProtocolVersion(min_version) => {
+                let state = self.state.lock().await;
+                match state
+                    .server_info
+                    .as_ref()
+                    .and_then(|info| info.protocol_version)
+                {
+                    Some(version) => version >= min_version,
+                    None => true,
+                }
+            }
+        }
+    }
+
+    async fn use_bundles(&self) -> bool {
+        self.bundle_packets
+            && self.protocol_mode == ProtocolMode::SlimeVr
+            && self.uses_modern_packets().await
+    }
+
+    fn is_legacy(&self) -> bool {
+        self.feature_flags == FirmwareFeatureFlags::LegacySlimevrTracker
+    }
+
+    // Both owoTrack and legacy SlimeVR trackers come from before sensor ids
+    fn single_sensor(&self) -> bool {
+        self.protocol_mode == ProtocolMode::OwoTrack || self.is_legacy()
+    }
+
+    fn flavour(&self) -> &'static str {
+        if self.protocol_mode == ProtocolMode::OwoTrack {
+            "owoTrack"
+        } else {
+            "Legacy"
+        }
+    }
+
+    async fn require_modern(&self, what: &str) -> Result<(), String> {
+        if self.uses_modern_packets().await {
+            Ok(())
+        } else {
+            Err(format!(
+                "{what} isn't supported by this server's protocol version"
+            ))
+        }
+    }
+
+    async fn data_target(&self) -> SocketAddr {
+        self.state.lock().await.data_target()
+    }
+
+    async fn record_sent(&self, bytes: &[u8]) {
+        self.state.lock().await.record_sent(bytes);
+    }
+
+    // Fails before init so a send that can't go out doesn't use up a sequence number
+    fn build_packet(
+        &self,
+        build: impl FnOnce(&mut TrackerCore) -> Result<Vec<u8>, String>,
+    ) -> Result<(u64, Vec<u8>), String> {
+        if self.socket.is_none() {
+            return Err(TrackerError::NotInitialized.into());
+        }
+        self.send_path.build(build)
+    }
+}
+
+// The background tasks only hold clones, so without this they'd keep the connection alive after the tracker is
+// gone. The state lock can't be awaited here, but the tasks all watch the status channel anyway.
+#[cfg(feature = "tokio")]
+impl Drop for EmulatedTracker {
+    fn drop(&mut self) {
+        if self.status_rx.borrow().is_stopped() {
+            return;
+        }
+        match self.state.try_lock() {
+            Ok(mut state) => state.set_status(TrackerStatus::Initializing, &self.status_tx),
+            Err(_) => {
+                let _ = self.status_tx.send(TrackerStatus::Initializing);
+            }
+        }
+    }
+}
+
+// What every packet the tracker sends goes through, from the tracker itself or from a background task (heartbeats,
+// ping replies, simulations, SensorInfo retries), so they all get the same dry_run, simulated network, packet history
+// and reconnect_after_failures treatment. Cheap to clone, it's mostly handles to the tracker's shared state.
+#[cfg(feature = "tokio")]
+#[derive(Clone)]
+pub(crate) struct SendPath {
+    pub(crate) state: Arc<Mutex<TrackerState>>,
+    // Numbers and builds every packet, so the background tasks share the tracker's sequence
+    pub(crate) core: Arc<std::sync::Mutex<TrackerCore>>,
+    pub(crate) status_tx: Arc<Sender<TrackerStatus>>,
+    pub(crate) status_rx: Receiver<TrackerStatus>,
+    pub(crate) event_tx: broadcast::Sender<TrackerEvent>,
+    pub(crate) debug: Arc<AtomicBool>,
+    // For the task spans
+    pub(crate) mac_address: [u8; 6],
+    // Packets are built and recorded but never sent, see the builder
+    pub(crate) dry_run: bool,
+    // Consecutive failed sends before going back to discovery, None keeps the connection regardless
+    pub(crate) reconnect_after_failures: Option<u32>,
+    // First and longest wait between handshakes while no server answers
+    pub(crate) handshake_backoff: (Duration, Duration),
+    // Handshakes sent quickly at the start of discovery and how far apart, before the backoff kicks in
+    pub(crate) handshake_burst: Option<(u32, Duration)>,
+    // Fake network conditions, loss is the chance (0-1) of dropping each datagram
+    #[cfg(feature = "testing")]
+    pub(crate) simulated_loss: f32,
+    #[cfg(feature = "testing")]
+    pub(crate) simulated_latency: Duration,
+    // Datagrams held back by simulated_latency that may not have gone out yet, see flush
+    #[cfg(feature = "testing")]
+    pub(crate) delayed_sends: Arc<std::sync::Mutex<Vec<JoinHandle<()>>>>,
+    // Here rather than on the tracker so the simulations can record what they send too, see attach_recorder
+    pub(crate) recorder: Arc<std::sync::Mutex<Option<Recorder>>>,
+}
+
+#[cfg(feature = "tokio")]
+impl SendPath {
+    // Builds the next packet with the core, returning the sequence number it got. The lock is only held while
+    // building, never across a send.
+    pub(crate) fn build(
+        &self,
+        build: impl FnOnce(&mut TrackerCore) -> Result<Vec<u8>, String>,
+    ) -> Result<(u64, Vec<u8>), String> {
+        let mut core = self.core.lock().unwrap();
+        let bytes = build(&mut core)?;
+        Ok((core.packet_number(), bytes))
+    }
+
+    // Numbers the packet in the tracker's sequence and sends it to the server
+    pub(crate) async fn send_packet(
+        &self,
+        socket: &Arc<UdpSocket>,
+        data: SbPacket,
+    ) -> Result<u64, String> {
+        let (packet_number, bytes) = self.build(|core| core.packet(data))?;
+        self.send(socket, packet_number, &bytes).await?;
+        Ok(packet_number)
+    }
+
+    // send_packet for telemetry the tracker would send itself, which goes in the recording too. Heartbeats and
+    // replies to the server stay out of it, the tracker sends those on its own while a recording is replayed.
+    pub(crate) async fn send_recorded(
+        &self,
+        socket: &Arc<UdpSocket>,
+        data: SbPacket,
+    ) -> Result<u64, String> {
+        let (packet_number, bytes) = self.build(|core| core.packet(data))?;
+        self.send(socket, packet_number, &bytes).await?;
+        self.record(packet_number, &bytes);
+        Ok(packet_number)
+    }
+
+    pub(crate) fn record(&self, packet_number: u64, bytes: &[u8]) {
+        if let Some(recorder) = self.recorder.lock().unwrap().as_ref() {
+            if let Err(e) = recorder.record(bytes) {
+                warn!(seq = packet_number, error = %e, "Failed to record packet");
+            }
+        }
+    }
+
+    // Heartbeats don't move the sequence on when numbering is frozen (testing only)
+    pub(crate) async fn send_heartbeat(
+        &self,
+        socket: &Arc<UdpSocket>,
+        frozen: bool,
+    ) -> Result<u64, String> {
+        if !frozen {
+            return self.send_packet(socket, SbPacket::Heartbeat).await;
+        }
+        let packet_number = self.core.lock().unwrap().packet_number();
+        let bytes = sans_io::encode(packet_number, SbPacket::Heartbeat)?;
+        self.send(socket, packet_number, &bytes).await?;
+        Ok(packet_number)
+    }
+
+    // Already serialized packet bytes with the sequence number swapped for a fresh one
+    pub(crate) async fn send_renumbered(
+        &self,
+        socket: &Arc<UdpSocket>,
+        bytes: &[u8],
+    ) -> Result<u64, String> {
+        let (packet_number, bytes) = self.build(|core| core.renumber(bytes))?;
+        self.send(socket, packet_number, &bytes).await?;
+        Ok(packet_number)
+    }
+
+    // Sends already serialized bytes to wherever data currently goes
+    pub(crate) async fn send(
+        &self,
+        socket: &Arc<UdpSocket>,
+        packet_number: u64,
+        bytes: &[u8],
+    ) -> Result<(), String> {
+        let target = self.state.lock().await.data_target();
+        self.send_to(socket, target, packet_number, bytes).await
+    }
+
+    // The latest handshake to the discovery address, always sequence number 0
+    pub(crate) async fn send_handshake(&self, socket: &Arc<UdpSocket>) -> Result<(), String> {
+        // Read every time, set_server may have moved the tracker to another server
+        let target = self.state.lock().await.discovery_addr;
+        let bytes = self.core.lock().unwrap().handshake()?;
+        // Not run through simulate_network, the simulated loss and latency are about the traffic once connected
+        self.transmit(socket, target, 0, &bytes, self.dry_run).await
+    }
+
+    pub(crate) async fn send_to(
+        &self,
+        socket: &Arc<UdpSocket>,
+        target: SocketAddr,
+        packet_number: u64,
+        bytes: &[u8],
+    ) -> Result<(), String> {
+        #[cfg(feature = "testing")]
+        let simulated = self.dry_run || self.simulate_network(socket, target, packet_number, bytes);
+        #[cfg(not(feature = "testing"))]
+        let simulated = self.dry_run;
+        self.transmit(socket, target, packet_number, bytes, simulated)
+            .await
+    }
+
+    // Puts the datagram on the socket unless dry_run/simulate_network already took care of it, and records it
+    // either way
+    async fn transmit(
+        &self,
+        socket: &Arc<UdpSocket>,
+        target: SocketAddr,
+        packet_number: u64,
+        bytes: &[u8],
+        simulated: bool,
+    ) -> Result<(), String> {
+        if self.debug.load(Ordering::Relaxed) {
+            trace!(
+                seq = packet_number,
+                peer = %target,
+                size = bytes.len(),
+                dry_run = self.dry_run,
+                "Sending datagram"
+            );
+        }
+        if !simulated {
+            let result = socket.send_to(bytes, target).await;
+            self.track_send_result(socket, result.is_ok()).await;
+            result.map_err(|e| e.to_string())?;
+        }
+
+        self.state.lock().await.record_sent(bytes);
+        Ok(())
+    }
+
+    // Drops or delays the datagram according to simulated_loss/simulated_latency. Returns true if it was dealt with
+    // here, in which case the tracker still counts it as sent (it can't tell the network lost it either).
+    // Delayed datagrams go out from a separate task so the caller isn't held up, and since the latency is the same
+    // for every packet they still arrive in order.
+    #[cfg(feature = "testing")]
+    fn simulate_network(
+        &self,
+        socket: &Arc<UdpSocket>,
+        target: SocketAddr,
+        packet_number: u64,
+        bytes: &[u8],
+    ) -> bool {
+        if self.simulated_loss > 0.0 && rand::random::<f32>() < self.simulated_loss {
+            if self.debug.load(Ordering::Relaxed) {
+                debug!(seq = packet_number, "Simulated loss, dropping packet");
+            }
+            return true;
+        }
+        if self.simulated_latency.is_zero() {
+            return false;
+        }
+
+        let socket = socket.clone();
+        let latency = self.simulated_latency;
+        let bytes = bytes.to_vec();
+        let handle = spawn_task(self.mac_address, "delayed_send", async move {
+            sleep(latency).await;
+            if let Err(e) = socket.send_to(&bytes, target).await {
+                warn!(seq = packet_number, error = %e, "Failed to send delayed packet");
+            }
+        });
+        let mut delayed_sends = self.delayed_sends.lock().unwrap();
+        delayed_sends.retain(|handle| !handle.is_finished());
+        delayed_sends.push(handle);
+        true
+    }
+
+    // After reconnect_after_failures sends in a row have failed, drop back to idle and look for the server again
+    async fn track_send_result(&self, socket: &Arc<UdpSocket>, succeeded: bool) {
+        let Some(max_failures) = self.reconnect_after_failures else {
+            return;
+        };
+        let mut state = self.state.lock().await;
+        if succeeded {
+            state.stats.consecutive_send_failures = 0;
+            return;
+        }
+        state.stats.consecutive_send_failures += 1;
+        if state.stats.consecutive_send_failures < max_failures
+            || state.status != TrackerStatus::Connected
+        {
+            return;
+        }
+
+        warn!(
+            failures = max_failures,
+            "Sending keeps failing, looking for the server again"
+        );
+        state.stats.consecutive_send_failures = 0;
+        state.stats.reconnects += 1;
+        state.set_status(TrackerStatus::Idle, &self.status_tx);
+        drop(state);
+        let _ = self.event_tx.send(TrackerEvent::Disconnected);
+        self.start_rediscovery(socket);
+    }
+
+    fn handshake_schedule(&self) -> HandshakeSchedule {
+        let (base, cap) = self.handshake_backoff;
+        let (count, spacing) = self.handshake_burst.unwrap_or_default();
+        HandshakeSchedule {
+            // The first handshake of the burst goes out right away
+            burst_left: count.saturating_sub(1),
+            spacing,
+            delay: base,
+            cap,
+        }
+    }
+
+    // Same handshake loop as init, but in the background since the tracker is already initialized. The receive
+    // task marks the tracker connected again as soon as the server answers, which ends it.
+    fn start_rediscovery(&self, socket: &Arc<UdpSocket>) {
+        let socket = socket.clone();
+        let send_path = self.clone();
+        let mut schedule = self.handshake_schedule();
+
+        spawn_task(self.mac_address, "rediscovery", async move {
+            while *send_path.status_rx.borrow() == TrackerStatus::Idle {
+                if let Err(e) = send_path.send_handshake(&socket).await {
+                    warn!(error = %e, "Failed to send handshake");
+                }
+                sleep(schedule.next_delay()).await;
+            }
+        });
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn spawn_task<F>(mac_address: [u8; 6], name: &'static str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(feature = "tracing")]
+    let future = tracing::Instrument::instrument(
+        future,
+        tracing::info_span!("tracker", mac = %mac::mac_to_string(mac_address), task = name),
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = mac_address;
+
+    #[cfg(all(tokio_unstable, feature = "tracing"))]
+    {
+        tokio::task::Builder::new()
+            .name(name)
+            .spawn(future)
+            .expect("Failed to spawn task")
+    }
+    #[cfg(not(all(tokio_unstable, feature = "tracing")))]
+    {
+        let _ = name;
+        tokio::spawn(future)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use firmware_protocol::deku::prelude::*;
+    use firmware_protocol::Packet;
+    use sans_io::{
+        PACKET_ACCELERATION, PACKET_BUNDLE, PACKET_COMMAND, PACKET_FLEX_DATA,
+        PACKET_ROTATION_LEGACY, PACKET_SENSOR_INFO,
+    };
+
+    // Packet type (u32) and sequence number (u64) header of a clientbound packet
+    fn cb_header(packet_type: u32, seq: u64) -> Vec<u8> {
+        let mut bytes = packet_type.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&seq.to_be_bytes());
+        bytes
+    }
+
+    // Stands in for a SlimeVR server on localhost, returns the socket and a builder pointed at it
+    async fn mock_server() -> (UdpSocket, EmulatedTrackerBuilder) {
+        let server = UdpSocket::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind mock server");
+        let port = server.local_addr().unwrap().port();
+        let builder = EmulatedTracker::builder()
+            .server_ip("127.0.0.1")
+            .server_discovery_port(port);
+        (server, builder)
+    }
+
+    // Runs init, answering the first packet from the tracker with a heartbeat so it connects
+    async fn connect(tracker: &mut EmulatedTracker, server: &UdpSocket) {
+        let answer = async {
+            let mut buf = [0u8; 1024];
+            let (_, addr) = server.recv_from(&mut buf).await.unwrap();
+            server.send_to(&cb_header(1, 0), addr).await.unwrap();
+        };
+        let (result, _) = tokio::join!(tracker.init(), answer);
+        result.expect("Failed to initialize tracker");
+    }
+
+    // Waits for the next packet of `packet_type` from the tracker, skipping anything else
+    async fn recv_packet(server: &UdpSocket, packet_type: u32) -> Vec<u8> {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            let mut buf = [0u8; 1024];
+            loop {
+                let (size, _) = server.recv_from(&mut buf).await.unwrap();
+                if size >= 12 && buf[..4] == packet_type.to_be_bytes() {
+                    return buf[..size].to_vec();
+                }
+            }
+        })
+        .await
+        .expect("Timed out waiting for packet")
+    }
+
+    #[tokio::test]
+    async fn test_legacy_tracker() {
+        let (server, builder) = mock_server().await;
+        let modern = EmulatedTracker::builder().build().unwrap();
+        let mut tracker = builder
+            .feature_flags(FirmwareFeatureFlags::LegacySlimevrTracker)
+            .auto_heartbeat(false)
+            .build()
+            .unwrap();
+        assert_eq!(tracker.config_snapshot().await.build_number, 8);
+        assert_eq!(
+            tracker.handshake().to_bytes().unwrap().len(),
+            modern.handshake().to_bytes().unwrap().len() - 6
+        );
+
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensors([
+                (ImuType::Bno085, SensorStatus::Ok),
+                (ImuType::Bno085, SensorStatus::Ok),
+            ])
+            .await
+            .unwrap();
+        assert!(!tracker.uses_modern_packets().await);
+        assert!(tracker
+            .send_rotation(1, SensorDataType::Normal, Quat::IDENTITY.into(), 0)
+            .await
+            .is_err());
+
+        // Header and the quaternion (x, y, z, w), no sensor id or data type
+        let quat = SlimeQuaternion {
+            i: 0.1,
+            j: 0.2,
+            k: 0.3,
+            w: 0.9,
+        };
+        let seq = tracker
+            .send_rotation(0, SensorDataType::Normal, quat, 0)
+            .await
+            .unwrap();
+        let rotation = recv_packet(&server, PACKET_ROTATION_LEGACY).await;
+        assert_eq!(rotation.len(), 28);
+        assert_eq!(rotation[4..12], seq.to_be_bytes());
+        for (i, value) in [0.1f32, 0.2, 0.3, 0.9].iter().enumerate() {
+            assert_eq!(rotation[12 + i * 4..16 + i * 4], value.to_be_bytes());
+        }
+
+        // Header and the vector, no sensor id
+        tracker
+            .send_acceleration(0, Vec3::new(1.0, 2.0, 3.0))
+            .await
+            .unwrap();
+        let acceleration = recv_packet(&server, PACKET_ACCELERATION).await;
+        assert_eq!(acceleration.len(), 24);
+        for (i, value) in [1.0f32, 2.0, 3.0].iter().enumerate() {
+            assert_eq!(acceleration[12 + i * 4..16 + i * 4], value.to_be_bytes());
+        }
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_hardware() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder
+            .board_type(BoardType::SlimeVR)
+            .mcu_type(McuType::Esp32C3)
+            .primary_imu(ImuType::Lsm6dsv)
+            .build_number(17)
+            .imu_info((1, 2, 3))
+            .firmware_version("test-fw")
+            .build()
+            .unwrap();
+
+        // The heartbeat task may get a packet out first, so wait for the handshake itself (type 3)
+        let answer = async {
+            let mut buf = [0u8; 1024];
+            loop {
+                let (size, addr) = server.recv_from(&mut buf).await.unwrap();
+                if buf[..4] == 3u32.to_be_bytes() {
+                    server.send_to(&cb_header(1, 0), addr).await.unwrap();
+                    return buf[..size].to_vec();
+                }
+            }
+        };
+        let (result, handshake) = tokio::join!(tracker.init(), answer);
+        result.unwrap();
+
+        let (_, packet) = Packet::<SbPacket>::from_bytes((&handshake, 0)).unwrap();
+        match packet.split().1 {
+            SbPacket::Handshake {
+                board,
+                imu,
+                mcu,
+                imu_info,
+                build,
+                firmware,
+                ..
+            } => {
+                assert_eq!(imu_info, (1, 2, 3));
+                assert_eq!(board, BoardType::SlimeVR);
+                assert_eq!(imu, ImuType::Lsm6dsv);
+                assert_eq!(mcu, McuType::Esp32C3);
+                assert_eq!(build, 17);
+                assert_eq!(firmware.to_string(), "test-fw");
+            }
+            other => panic!("Expected a handshake, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_without_primary_imu() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        let answer = async {
+            let mut buf = [0u8; 1024];
+            loop {
+                let (size, addr) = server.recv_from(&mut buf).await.unwrap();
+                if buf[..4] == 3u32.to_be_bytes() {
+                    server.send_to(&cb_header(1, 0), addr).await.unwrap();
+                    return buf[..size].to_vec();
+                }
+            }
+        };
+        let (result, handshake) = tokio::join!(tracker.init(), answer);
+        result.unwrap();
+
+        let imu_of = |bytes: &[u8]| {
+            let (_, packet) = Packet::<SbPacket>::from_bytes((bytes, 0)).unwrap();
+            match packet.split().1 {
+                SbPacket::Handshake { imu, .. } => imu.id(),
+                other => panic!("Expected a handshake, got {other:?}"),
+            }
+        };
+        // No sensors yet when init sends it
+        assert_eq!(imu_of(&handshake), 0);
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        tracker.resend_handshake().await.unwrap();
+        assert_eq!(imu_of(&recv_packet(&server, 3).await), ImuType::Bno085.id());
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resend_handshake() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        recv_packet(&server, PACKET_SENSOR_INFO).await;
+
+        tracker.resend_handshake().await.unwrap();
+        let handshake = recv_packet(&server, 3).await;
+        assert_eq!(handshake[4..12], 0u64.to_be_bytes());
+        assert_eq!(recv_packet(&server, PACKET_SENSOR_INFO).await[12], 0);
+        assert!(tracker.is_connected().await);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_too_many_sensors() {
+        let mut tracker = EmulatedTracker::builder().dry_run(true).build().unwrap();
+        tracker.init().await.unwrap();
+        let specs = |count| (0..count).map(|_| (ImuType::Bno085, SensorStatus::Ok));
+        assert_eq!(
+            tracker.add_sensors(specs(MAX_SENSORS + 1)).await,
+            Err(TrackerError::TooManySensors.to_string())
+        );
+        assert!(tracker.sensors.is_empty());
+
+        tracker.add_sensors(specs(MAX_SENSORS)).await.unwrap();
+        assert_eq!(
+            tracker.add_sensor(ImuType::Bno085, SensorStatus::Ok).await,
+            Err(TrackerError::TooManySensors.to_string())
+        );
+        assert_eq!(
+            tracker.add_sensors(specs(1)).await,
+            Err(TrackerError::TooManySensors.to_string())
+        );
+        assert_eq!(tracker.sensors.len(), MAX_SENSORS);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_add_sensors_bundled() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.bundle_packets(true).build().unwrap();
+        connect(&mut tracker, &server).await;
+
+        let ids = tracker
+            .add_sensors([
+                (ImuType::Bno085, SensorStatus::Ok),
+                (ImuType::Bno085, SensorStatus::Ok),
+                (ImuType::Icm45686, SensorStatus::Offline),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![0, 1, 2]);
+
+        // Each entry: length (u16), packet type (u32), sensor id, sensor type, status
+        let bundle = recv_packet(&server, PACKET_BUNDLE).await;
+        let mut rest = &bundle[12..];
+        let mut sensor_ids = Vec::new();
+        while !rest.is_empty() {
+            let len = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+            assert_eq!(rest[2..6], PACKET_SENSOR_INFO.to_be_bytes());
+            sensor_ids.push(rest[6]);
+            rest = &rest[2 + len..];
+        }
+        assert_eq!(sensor_ids, ids);
+    }
+
+    #[tokio::test]
+    async fn test_connected_sensor_ids() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensors([
+                (ImuType::Bno085, SensorStatus::Ok),
+                (ImuType::Bno085, SensorStatus::Offline),
+                (ImuType::Bno085, SensorStatus::Ok),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(tracker.connected_sensor_ids(), vec![0, 2]);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_announce_sensors() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        tracker
+            .add_sensor(ImuType::Icm45686, SensorStatus::Offline)
+            .await
+            .unwrap();
+        recv_packet(&server, PACKET_SENSOR_INFO).await;
+        recv_packet(&server, PACKET_SENSOR_INFO).await;
+
+        tracker.announce_sensors().await.unwrap();
+        // Sensor id right after the header
+        let first = recv_packet(&server, PACKET_SENSOR_INFO).await;
+        assert_eq!(first[12], 0);
+        let second = recv_packet(&server, PACKET_SENSOR_INFO).await;
+        assert_eq!(second[12], 1);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_rotation_all_bundled() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.bundle_packets(true).build().unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensors([
+                (ImuType::Bno085, SensorStatus::Ok),
+                (ImuType::Bno085, SensorStatus::Ok),
+            ])
+            .await
+            .unwrap();
+        recv_packet(&server, PACKET_BUNDLE).await;
+
+        let quat = SlimeQuaternion {
+            i: 0.0,
+            j: 0.0,
+            k: 0.0,
+            w: 1.0,
+        };
+        let seqs = tracker
+            .send_rotation_all(SensorDataType::Normal, quat, 0)
+            .await
+            .unwrap();
+        assert_eq!(seqs.len(), 2);
+        assert_eq!(seqs[0], seqs[1]);
+
+        // One RotationData (type 17) entry per sensor, sensor id right after the packet type
+        let bundle = recv_packet(&server, PACKET_BUNDLE).await;
+        assert_eq!(bundle[4..12], seqs[0].to_be_bytes());
+        let mut rest = &bundle[12..];
+        let mut sensor_ids = Vec::new();
+        while !rest.is_empty() {
+            let len = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+            assert_eq!(rest[2..6], 17u32.to_be_bytes());
+            sensor_ids.push(rest[6]);
+            rest = &rest[2 + len..];
+        }
+        assert_eq!(sensor_ids, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_send_imu_frame() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.bundle_packets(true).build().unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+
+        let (rotation_seq, acceleration_seq) = tracker
+            .send_imu_frame(0, Quat::IDENTITY.into(), 0, Vec3::new(0.0, 0.0, 9.8))
+            .await
+            .unwrap();
+        assert_eq!(rotation_seq, acceleration_seq);
+
+        // RotationData (17) then Acceleration (4)
+        let bundle = recv_packet(&server, PACKET_BUNDLE).await;
+        assert_eq!(bundle[4..12], rotation_seq.to_be_bytes());
+        let mut rest = &bundle[12..];
+        let mut packet_types = Vec::new();
+        while !rest.is_empty() {
+            let len = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+            packet_types.push(u32::from_be_bytes(rest[2..6].try_into().unwrap()));
+            rest = &rest[2 + len..];
+        }
+        assert_eq!(packet_types, [17, PACKET_ACCELERATION]);
+    }
+
+    #[tokio::test]
+    async fn test_bundle_split() {
+        let (server, builder) = mock_server().await;
+        // RotationData takes 25 bytes in a bundle, so 3 fit after the header
+        let mut tracker = builder
+            .auto_heartbeat(false)
+            .bundle_packets(true)
+            .max_datagram_size(100)
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensors((0..5).map(|_| (ImuType::Bno085, SensorStatus::Ok)))
+            .await
+            .unwrap();
+
+        let seqs = tracker
+            .send_rotation_all(SensorDataType::Normal, Quat::IDENTITY.into(), 0)
+            .await
+            .unwrap();
+        assert_eq!(seqs[..3], [seqs[0]; 3]);
+        assert_eq!(seqs[3..], [seqs[0] + 1; 2]);
+
+        // Skips the SensorInfo bundle from add_sensors
+        let mut sizes = HashMap::new();
+        while !sizes.contains_key(&seqs[3]) {
+            let bundle = recv_packet(&server, PACKET_BUNDLE).await;
+            let seq = u64::from_be_bytes(bundle[4..12].try_into().unwrap());
+            sizes.insert(seq, bundle.len());
+        }
+        assert_eq!(sizes[&seqs[0]], 12 + 3 * 25);
+        assert_eq!(sizes[&seqs[3]], 12 + 2 * 25);
+    }
+
+    #[tokio::test]
+    async fn test_second_server_ignored() {
+        let (server, builder) = mock_server().await;
+        let other = UdpSocket::bind("127.0.0.2:0").await.unwrap();
+        let mut tracker = builder.build().unwrap();
+        let mut events = tracker.subscribe_events();
+        connect(&mut tracker, &server).await;
+
+        // The second server shows up as a candidate, but the tracker stays with the first
+        let tracker_addr = SocketAddr::new(
+            "127.0.0.1".parse().unwrap(),
+            tracker
+                .socket
+                .as_ref()
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port(),
+        );
+        other.send_to(&cb_header(1, 0), tracker_addr).await.unwrap();
+        let servers = loop {
+            match events.recv().await.unwrap() {
+                TrackerEvent::ServersDiscovered(servers) if servers.len() == 2 => break servers,
+                _ => {}
+            }
+        };
+        assert_eq!(servers[0], server.local_addr().unwrap());
+        assert_eq!(servers[1], other.local_addr().unwrap());
+        assert_eq!(
+            tracker.get_state().await.server_addr,
+            Some(server.local_addr().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drop_stops_tasks() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        let status_rx = tracker.subscribe_status();
+
+        drop(tracker);
+        assert_eq!(*status_rx.borrow(), TrackerStatus::Initializing);
+    }
+
+    #[tokio::test]
+    async fn test_run_until() {
+        let (_server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+
+        // Nobody answers, so this only returns because of the shutdown
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            tracker.run_until(sleep(Duration::from_millis(50))),
+        )
+        .await
+        .expect("run_until ignored the shutdown");
+        result.unwrap();
+        assert_eq!(
+            tracker.get_state().await.status,
+            TrackerStatus::Initializing
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_deterministic_numbering() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.freeze_heartbeat_numbering(true).build().unwrap();
+        connect(&mut tracker, &server).await;
+
+        tracker.set_packet_number(41);
+        // A heartbeat from the server gets answered without using up a number
+        tracker.handle_packet(&cb_header(1, 0)).await.unwrap();
+        tracker.send_user_action(ActionType::Reset).await.unwrap();
+
+        let action = recv_packet(&server, 21).await;
+        assert_eq!(action[4..12], 42u64.to_be_bytes());
+        assert_eq!(tracker.packet_number(), 42);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_replies_use_send_options() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.simulated_loss(1.0).build().unwrap();
+        connect(&mut tracker, &server).await;
+        let sent = tracker.stats().await.packets_sent;
+
+        // Heartbeat and ping replies get lost like anything else the tracker sends
+        tracker.handle_packet(&cb_header(1, 0)).await.unwrap();
+        let mut ping = cb_header(10, 1);
+        ping.extend_from_slice(&[1, 2, 3, 4]);
+        tracker.handle_packet(&ping).await.unwrap();
+        // The heartbeat task may have sent one of its own in between
+        assert!(tracker.stats().await.packets_sent >= sent + 2);
+        let mut buf = [0u8; 1024];
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), server.recv_from(&mut buf))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_config_snapshot() {
+        let tracker = EmulatedTracker::builder()
+            .mac_address([1, 2, 3, 4, 5, 6])
+            .board_type(BoardType::SlimeVR)
+            .server_ipv4([10, 0, 0, 2], 7000)
+            .server_timeout_ms(2000)
+            .build()
+            .unwrap();
+        let config = tracker.config_snapshot().await;
+        assert_eq!(config.mac_address, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(config.board_type, BoardType::SlimeVR);
+        assert_eq!(config.server_addr, "10.0.0.2:7000".parse().unwrap());
+        assert_eq!(config.server_timeout, Duration::from_secs(2));
+        assert_eq!(config.sensor_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_server_addr() {
+        let tracker = EmulatedTracker::builder()
+            .server_ipv4([192, 168, 1, 10], 7000)
+            .build()
+            .unwrap();
+        assert_eq!(
+            tracker.config_snapshot().await.server_addr,
+            "192.168.1.10:7000".parse().unwrap()
+        );
+
+        let tracker = EmulatedTracker::builder()
+            .server_ip("10.0.0.1")
+            .build()
+            .unwrap();
+        assert_eq!(
+            tracker.config_snapshot().await.server_addr,
+            "10.0.0.1:6969".parse().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_target() {
+        let tracker = EmulatedTracker::builder().build().unwrap();
+        assert_eq!(tracker.send_target().await, SendTarget::Broadcast);
+        let subnet = Ipv4Addr::new(192, 168, 1, 255);
+        let tracker = EmulatedTracker::builder()
+            .broadcast_address(subnet)
+            .build()
+            .unwrap();
+        assert_eq!(tracker.send_target().await, SendTarget::Broadcast);
+        // Looks like the broadcast address of a /29, but nothing said it was one
+        let tracker = EmulatedTracker::builder()
+            .server_ip("192.168.1.7")
+            .build()
+            .unwrap();
+        assert_eq!(
+            tracker.send_target().await,
+            SendTarget::Unicast("192.168.1.7:6969".parse().unwrap())
+        );
+
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        assert_eq!(
+            tracker.get_state().await.send_target,
+            SendTarget::Unicast(server.local_addr().unwrap())
+        );
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_state() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.auto_heartbeat(false).build().unwrap();
+        let mut state_rx = tracker.subscribe_state();
+        assert_eq!(
+            state_rx.borrow_and_update().status,
+            TrackerStatus::Initializing
+        );
+        connect(&mut tracker, &server).await;
+        {
+            let state = state_rx.borrow_and_update();
+            assert_eq!(state.status, TrackerStatus::Connected);
+            assert_eq!(state.stats.packets_received, 1);
+            assert_eq!(
+                state.send_target,
+                SendTarget::Unicast(server.local_addr().unwrap())
+            );
+        }
+
+        let seq = tracker.send_heartbeat().await.unwrap();
+        assert!(state_rx.has_changed().unwrap());
+        assert_eq!(state_rx.borrow_and_update().packet_number, seq);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[test]
+    fn test_firmware_version_length() {
+        let too_long = "a".repeat(MAX_FIRMWARE_VERSION_LEN + 1);
+        assert!(matches!(
+            EmulatedTracker::builder()
+                .firmware_version(too_long)
+                .build(),
+            Err(TrackerError::InvalidFirmwareVersion(256))
+        ));
+        let longest = "a".repeat(MAX_FIRMWARE_VERSION_LEN);
+        assert!(EmulatedTracker::builder()
+            .firmware_version(longest)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_require_broadcast() {
+        // Only the default address, so broadcast is best effort
+        assert!(
+            !EmulatedTracker::builder()
+                .build()
+                .unwrap()
+                .require_broadcast
+        );
+        assert!(
+            !EmulatedTracker::builder()
+                .server_ip("127.0.0.1")
+                .build()
+                .unwrap()
+                .require_broadcast
+        );
+        assert!(
+            EmulatedTracker::builder()
+                .broadcast_address(Ipv4Addr::new(192, 168, 1, 255))
+                .build()
+                .unwrap()
+                .require_broadcast
+        );
+        assert!(
+            EmulatedTracker::builder()
+                .server_ip("255.255.255.255")
+                .build()
+                .unwrap()
+                .require_broadcast
+        );
+        // Host part is all ones for a /29, but it was given as a server
+        assert!(
+            !EmulatedTracker::builder()
+                .server_ip("192.168.1.7")
+                .build()
+                .unwrap()
+                .require_broadcast
+        );
+    }
+
+    #[tokio::test]
+    async fn test_socket_buffers() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder
+            .socket_send_buffer(64 * 1024)
+            .socket_recv_buffer(64 * 1024)
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+        let socket = tracker.socket.clone().unwrap();
+        let sock_ref = socket2::SockRef::from(socket.as_ref());
+        // The OS is free to round the size up (Linux doubles it)
+        assert!(sock_ref.send_buffer_size().unwrap() >= 64 * 1024);
+        assert!(sock_ref.recv_buffer_size().unwrap() >= 64 * 1024);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        let report = tracker.health_check().await;
+        assert!(!report.socket_bound);
+        assert!(!report.is_healthy());
+
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        let report = tracker.health_check().await;
+        assert!(report.is_healthy());
+        assert_eq!(report.sensors_registered, 1);
+        assert_eq!(report.sensors_acknowledged, 0);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pause() {
+        let (server, builder) = mock_server().await;
+        // No heartbeats so nothing else moves the sequence number
+        let mut tracker = builder.auto_heartbeat(false).build().unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+
+        tracker.pause();
+        let before = tracker.packet_number();
+        assert_eq!(tracker.send_acceleration(0, Vec3::ZERO).await, Ok(before));
+        tracker.send_ping().await.unwrap();
+        assert_eq!(tracker.packet_number(), before + 1);
+
+        tracker.resume();
+        let seq = tracker.send_acceleration(0, Vec3::ZERO).await.unwrap();
+        assert!(seq > before + 1);
+        assert_eq!(recv_packet(&server, 4).await[4..12], seq.to_be_bytes());
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_data_rate() {
+        for rate_hz in [0.0, -10.0, f32::NAN, f32::INFINITY, 1e-40] {
+            assert!(matches!(
+                EmulatedTracker::builder().data_rate_hz(rate_hz).build(),
+                Err(TrackerError::InvalidRate(_))
+            ));
+        }
+
+        let clock = clock::MockClock::new();
+        let mut tracker = EmulatedTracker::builder()
+            .dry_run(true)
+            .data_rate_hz(10.0)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        tracker.init().await.unwrap();
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        let seq = tracker.send_acceleration(0, Vec3::ZERO).await.unwrap();
+        assert_eq!(
+            tracker.send_acceleration(0, Vec3::ZERO).await,
+            Err(TrackerError::Throttled(0).to_string())
+        );
+        assert_eq!(tracker.stats().await.throttled_packets, 1);
+        // Rotation has a slot of its own
+        assert!(tracker
+            .send_imu_frame(0, Quat::IDENTITY.into(), 0, Vec3::ZERO)
+            .await
+            .is_ok());
+        assert!(tracker
+            .send_imu_frame(0, Quat::IDENTITY.into(), 0, Vec3::ZERO)
+            .await
+            .is_err());
+
+        // The interval goes by the tracker's clock
+        clock.advance(Duration::from_millis(99));
+        assert!(tracker.send_acceleration(0, Vec3::ZERO).await.is_err());
+        clock.advance(Duration::from_millis(2));
+        assert!(tracker.send_acceleration(0, Vec3::ZERO).await.unwrap() > seq);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_last_rotation() {
+        let mut tracker = EmulatedTracker::builder().dry_run(true).build().unwrap();
+        tracker.init().await.unwrap();
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        assert!(tracker.last_rotation(0).is_none());
+
+        let rotation = Quat::from_xyzw(0.0, 0.0, 0.6, 0.8);
+        tracker
+            .send_rotation(0, SensorDataType::Normal, rotation.into(), 0)
+            .await
+            .unwrap();
+        tracker
+            .send_correction(0, Quat::IDENTITY.into(), 0)
+            .await
+            .unwrap();
+        tracker.pause();
+        tracker
+            .send_rotation(0, SensorDataType::Normal, Quat::IDENTITY.into(), 0)
+            .await
+            .unwrap();
+        assert_eq!(tracker.last_rotation(0).map(Quat::from), Some(rotation));
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_separate_data_socket() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder
+            .auto_heartbeat(false)
+            .separate_data_socket(true)
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+
+        let control_port = tracker
+            .socket
+            .as_ref()
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        tracker.send_acceleration(0, Vec3::ZERO).await.unwrap();
+        let data_port = tokio::time::timeout(Duration::from_secs(5), async {
+            let mut buf = [0u8; 1024];
+            loop {
+                let (size, addr) = server.recv_from(&mut buf).await.unwrap();
+                if size >= 4 && buf[..4] == 4u32.to_be_bytes() {
+                    break addr.port();
+                }
+            }
+        })
+        .await
+        .expect("Timed out waiting for acceleration");
+        assert_ne!(data_port, control_port);
+
+        tracker.deinit().await.unwrap();
+        assert!(tracker.data_socket.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_packet_history() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder
+            .auto_heartbeat(false)
+            .packet_history(2)
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+
+        tracker.send_ping().await.unwrap();
+        tracker.send_heartbeat().await.unwrap();
+        let sent = tracker.last_sent().await;
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1], recv_packet(&server, 0).await);
+        assert_eq!(tracker.last_received().await, vec![cb_header(1, 0)]);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.dry_run(true).packet_history(8).build().unwrap();
+        let handshake = tracker.handshake().to_bytes().unwrap();
+        // Nobody answers and it connects anyway
+        tokio::time::timeout(Duration::from_secs(5), tracker.init())
+            .await
+            .expect("init waited for a server")
+            .unwrap();
+        assert!(tracker.is_connected().await);
+
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        let seq = tracker.send_acceleration(0, Vec3::ZERO).await.unwrap();
+        let sent = tracker.last_sent().await;
+        assert_eq!(sent[0], handshake);
+        let acceleration = sent.last().unwrap();
+        assert_eq!(acceleration[..4], PACKET_ACCELERATION.to_be_bytes());
+        assert_eq!(acceleration[4..12], seq.to_be_bytes());
+
+        let mut buf = [0u8; 1024];
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), server.recv_from(&mut buf))
+                .await
+                .is_err()
+        );
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_simulation_dry_run() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.dry_run(true).build().unwrap();
+        tracker.init().await.unwrap();
+        let sent = tracker.stats().await.packets_sent;
+
+        let handle = tracker
+            .start_battery_simulation(100.0, 1.0, simulation::lipo_voltage)
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // Counted like any other dry run send, just never on the wire
+        assert!(tracker.stats().await.packets_sent > sent);
+        let mut buf = [0u8; 1024];
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), server.recv_from(&mut buf))
+                .await
+                .is_err()
+        );
+        handle.abort();
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_battery_simulation() {
+        let mut tracker = EmulatedTracker::builder().dry_run(true).build().unwrap();
+        tracker.init().await.unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "tracker_emulation-rs-battery-{}.rec",
+            std::process::id()
+        ));
+        tracker.attach_recorder(Recorder::create(&path).unwrap());
+
+        // Checked like send_battery_level, so a curve past MAX_BATTERY_VOLTAGE sends nothing
+        let sent = tracker.stats().await.packets_sent;
+        let handle = tracker
+            .start_battery_simulation(100.0, 1.0, |_| MAX_BATTERY_VOLTAGE + 1.0)
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        handle.abort();
+        assert_eq!(tracker.stats().await.packets_sent, sent);
+
+        let handle = tracker
+            .start_battery_simulation(100.0, 1.0, simulation::lipo_voltage)
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        handle.abort();
+        drop(tracker.detach_recorder());
+        let packets = record::read_recording(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(!packets.is_empty());
+        assert!(packets
+            .iter()
+            .all(|packet| packet.data[..4] == 12u32.to_be_bytes()));
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_set_server() {
+        let (old_server, builder) = mock_server().await;
+        let mut tracker = builder.auto_heartbeat(false).build().unwrap();
+        connect(&mut tracker, &old_server).await;
+
+        let (new_server, _) = mock_server().await;
+        let mut status_rx = tracker.subscribe_status();
+        tracker
+            .set_server(new_server.local_addr().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(*status_rx.borrow_and_update(), TrackerStatus::Idle);
+        assert_eq!(
+            tracker.config_snapshot().await.server_addr,
+            new_server.local_addr().unwrap()
+        );
+
+        let mut buf = [0u8; 1024];
+        let (size, addr) = new_server.recv_from(&mut buf).await.unwrap();
+        assert_eq!(buf[..size], tracker.handshake().to_bytes().unwrap());
+        new_server.send_to(&cb_header(1, 0), addr).await.unwrap();
+        tracker.connected().await;
+
+        tracker.send_heartbeat().await.unwrap();
+        recv_packet(&new_server, 0).await;
+        tracker.deinit().await.unwrap();
+    }
+
+    #[test]
+    fn test_handshake_burst() {
+        let tracker = EmulatedTracker::builder()
+            .handshake_burst(3, Duration::from_millis(100))
+            .handshake_backoff(Duration::from_secs(1), Duration::from_secs(2))
+            .build()
+            .unwrap();
+        let mut schedule = tracker.send_path.handshake_schedule();
+        let delays: Vec<_> = (0..5).map(|_| schedule.next_delay()).collect();
+        assert_eq!(
+            delays,
+            [100, 100, 1000, 2000, 2000].map(Duration::from_millis)
+        );
+    }
+
+    #[test]
+    fn test_handshake_backoff() {
+        // A zero base would never back off and flood the network with handshakes
+        assert!(matches!(
+            EmulatedTracker::builder()
+                .handshake_backoff(Duration::ZERO, Duration::from_secs(1))
+                .build(),
+            Err(TrackerError::InvalidHandshakeBackoff)
+        ));
+
+        // A cap below the base is raised to it
+        let tracker = EmulatedTracker::builder()
+            .handshake_backoff(Duration::from_secs(2), Duration::from_secs(1))
+            .build()
+            .unwrap();
+        let mut schedule = tracker.send_path.handshake_schedule();
+        let delays: Vec<_> = (0..3).map(|_| schedule.next_delay()).collect();
+        assert_eq!(delays, [Duration::from_secs(2); 3]);
+    }
+
+    #[tokio::test]
+    async fn test_keep_socket_on_deinit() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.keep_socket_on_deinit(true).build().unwrap();
+        connect(&mut tracker, &server).await;
+        let port = tracker
+            .socket
+            .as_ref()
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        tracker.deinit().await.unwrap();
+        connect(&mut tracker, &server).await;
+        let new_port = tracker
+            .socket
+            .as_ref()
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        assert_eq!(port, new_port);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_battery_level() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+
+        tracker.send_battery_level(150.0, 4.2).await.unwrap();
+        let bytes = recv_packet(&server, 12).await;
+        let (_, packet) = Packet::<SbPacket>::from_bytes((&bytes, 0)).unwrap();
+        match packet.split().1 {
+            SbPacket::Battery {
+                percentage,
+                voltage,
+            } => {
+                assert_eq!(percentage, 100.0);
+                assert_eq!(voltage, 4.2);
+            }
+            other => panic!("Expected a battery packet, got {other:?}"),
+        }
+
+        assert!(tracker.send_battery_level(50.0, -1.0).await.is_err());
+        assert!(tracker.send_battery_level(50.0, 42.0).await.is_err());
+        assert!(tracker.send_battery_level(f32::NAN, 4.0).await.is_err());
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disconnected() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.server_timeout_ms(100).build().unwrap();
+        connect(&mut tracker, &server).await;
+
+        // The server never sends another heartbeat, so the watchdog gives up
+        tokio::time::timeout(Duration::from_secs(5), tracker.disconnected())
+            .await
+            .expect("Tracker never noticed the server was gone");
+        assert!(!tracker.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn test_world_frame_acceleration() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        assert!(tracker
+            .send_acceleration_in(0, (1.0, 0.0, 0.0), AccelFrame::World)
+            .await
+            .is_err());
+
+        // Sensor turned 90 degrees around z, so world x is the sensor's -y
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        tracker
+            .send_rotation(
+                0,
+                SensorDataType::Normal,
+                Quat::from_xyzw(0.0, 0.0, half, half).into(),
+                0,
+            )
+            .await
+            .unwrap();
+        tracker
+            .send_acceleration_in(0, (1.0, 0.0, 0.0), AccelFrame::World)
+            .await
+            .unwrap();
+        let bytes = recv_packet(&server, 4).await;
+        let (_, packet) = Packet::<SbPacket>::from_bytes((&bytes, 0)).unwrap();
+        match packet.split().1 {
+            SbPacket::Acceleration { vector, .. } => {
+                assert!(vector.0.abs() < 1e-6);
+                assert!((vector.1 + 1.0).abs() < 1e-6);
+            }
+            other => panic!("Expected an acceleration packet, got {other:?}"),
+        }
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rotation_jump_guard() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder
+            .rotation_jump_guard(360.0, JumpAction::Reject)
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+
+        let flipped = Quat::from_xyzw(1.0, 0.0, 0.0, 0.0);
+        tracker
+            .send_rotation(0, SensorDataType::Normal, Quat::IDENTITY.into(), 0)
+            .await
+            .unwrap();
+        // Half a turn within a few milliseconds
+        assert!(tracker
+            .send_rotation(0, SensorDataType::Normal, flipped.into(), 0)
+            .await
+            .is_err());
+        // Corrections aren't checked, and staying put is fine
+        tracker.send_correction(0, flipped.into(), 0).await.unwrap();
+        tracker
+            .send_rotation(0, SensorDataType::Normal, Quat::IDENTITY.into(), 0)
+            .await
+            .unwrap();
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_try_deinit() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.keep_socket_on_deinit(true).build().unwrap();
+        assert!(tracker.try_deinit().await.is_err());
+
+        connect(&mut tracker, &server).await;
+        tracker.try_deinit().await.unwrap();
+        assert!(tracker.try_deinit().await.is_err());
+        assert!(tracker.socket.is_some());
+
+        tracker.force_deinit().await;
+        assert!(tracker.socket.is_none());
+        assert_eq!(
+            tracker.get_state().await.status,
+            TrackerStatus::Initializing
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_before_init() {
+        let (_server, builder) = mock_server().await;
+        let tracker = builder.build().unwrap();
+
+        let quat = SlimeQuaternion {
+            i: 0.0,
+            j: 0.0,
+            k: 0.0,
+            w: 1.0,
+        };
+        let result = tracker
+            .send_rotation(0, SensorDataType::Normal, quat, 0)
+            .await;
+        assert_eq!(result, Err(TrackerError::NotInitialized.to_string()));
+        assert_eq!(tracker.get_state().await.packet_number, 0);
+    }
+
+    #[tokio::test]
+    async fn test_update_sensor() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        tracker.update_sensor(0, ImuType::Lsm6dsv).await.unwrap();
+        assert_eq!(
+            tracker.update_sensor(1, ImuType::Lsm6dsv).await,
+            Err(TrackerError::UnknownSensor(1).to_string())
+        );
+
+        // Sensor id then type after the header
+        let added = recv_packet(&server, PACKET_SENSOR_INFO).await;
+        let updated = recv_packet(&server, PACKET_SENSOR_INFO).await;
+        assert_eq!((added[12], updated[12]), (0, 0));
+        assert_ne!(added[13], updated[13]);
+        assert_eq!(tracker.sensors[0].imu_id, ImuType::Lsm6dsv.id());
+    }
+
+    #[tokio::test]
+    async fn test_send_temperature() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        assert_eq!(
+            tracker.send_temperature(0, 21.5).await,
+            Err(TrackerError::UnknownSensor(0).to_string())
+        );
+
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        let seq = tracker.send_temperature(0, 21.5).await.unwrap();
+        let packet = recv_packet(&server, 20).await;
+        assert_eq!(packet[4..12], seq.to_be_bytes());
+        assert_eq!(packet[12], 0);
+        assert_eq!(packet[13..17], 21.5f32.to_be_bytes());
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_data_follows_server_port() {
+        let (discovery, builder) = mock_server().await;
+        let data = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut tracker = builder.build().unwrap();
+
+        // Handshake arrives on the discovery port, the server answers from its data port
+        let answer = async {
+            let mut buf = [0u8; 1024];
+            let (_, addr) = discovery.recv_from(&mut buf).await.unwrap();
+            data.send_to(&cb_header(1, 0), addr).await.unwrap();
+        };
+        let (result, _) = tokio::join!(tracker.init(), answer);
+        result.unwrap();
+
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        let sensor_info = recv_packet(&data, PACKET_SENSOR_INFO).await;
+        assert_eq!(sensor_info[12], 0);
+        assert_eq!(
+            tracker.get_state().await.server_addr,
+            Some(data.local_addr().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout_updates_status() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.server_timeout_ms(100).build().unwrap();
+        let mut status_rx = tracker.subscribe_status();
+        let mut events = tracker.subscribe_events();
+        connect(&mut tracker, &server).await;
+        assert_eq!(*status_rx.borrow_and_update(), TrackerStatus::Connected);
+
+        // The mock server never sends another heartbeat, so the watchdog should give up on it
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while *status_rx.borrow_and_update() != TrackerStatus::Disconnected {
+                status_rx.changed().await.unwrap();
+            }
+        })
+        .await
+        .expect("Subscriber never saw the timeout");
+
+        assert_eq!(
+            tracker.get_state().await.status,
+            TrackerStatus::Disconnected
+        );
+        assert_eq!(tracker.stats().await.reconnects, 1);
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            TrackerEvent::ServersDiscovered(_)
+        ));
+        assert_eq!(events.recv().await.unwrap(), TrackerEvent::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_follows_clock() {
+        let (server, builder) = mock_server().await;
+        let clock = clock::MockClock::new();
+        let mut tracker = builder
+            .server_timeout_ms(50)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+
+        // No heartbeats, but no time has passed as far as the watchdog knows
+        sleep(Duration::from_millis(200)).await;
+        assert!(tracker.is_connected().await);
+        assert_eq!(tracker.time_since_heartbeat().await, Some(Duration::ZERO));
+
+        clock.advance(Duration::from_secs(1));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while tracker.is_connected().await {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Watchdog ignored the clock");
+    }
+
+    #[tokio::test]
+    async fn test_uptime() {
+        let (server, builder) = mock_server().await;
+        let clock = clock::MockClock::new();
+        let mut tracker = builder
+            .server_timeout_ms(60_000)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        assert_eq!(tracker.uptime().await, None);
+        connect(&mut tracker, &server).await;
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(tracker.uptime().await, Some(Duration::from_secs(5)));
+        assert_eq!(
+            tracker.connected_duration().await,
+            Some(Duration::from_secs(5))
+        );
+
+        tracker.deinit().await.unwrap();
+        assert_eq!(tracker.uptime().await, None);
+        assert_eq!(tracker.connected_duration().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_round_trips_follow_clock() {
+        let (server, builder) = mock_server().await;
+        let clock = clock::MockClock::new();
+        let mut tracker = builder
+            .auto_heartbeat(false)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+
+        tracker.send_heartbeat().await.unwrap();
+        clock.advance(Duration::from_millis(30));
+        tracker.handle_packet(&cb_header(1, 1)).await.unwrap();
+        assert_eq!(
+            tracker.stats().await.last_heartbeat_rtt,
+            Some(Duration::from_millis(30))
+        );
+
+        tracker.send_ping().await.unwrap();
+        let (challenge, _) = tracker.state.lock().await.stats.pending_ping.unwrap();
+        clock.advance(Duration::from_millis(20));
+        let mut echo = cb_header(10, 2);
+        echo.extend_from_slice(&challenge);
+        tracker.handle_packet(&echo).await.unwrap();
+        assert_eq!(
+            tracker.stats().await.last_ping_rtt,
+            Some(Duration::from_millis(20))
+        );
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_no_auto_heartbeat() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.auto_heartbeat(false).build().unwrap();
+        connect(&mut tracker, &server).await;
+
+        let sent = tracker.stats().await.packets_sent;
+        tracker.handle_packet(&cb_header(1, 1)).await.unwrap();
+        assert!(tracker.time_since_heartbeat().await.is_some());
+        assert_eq!(tracker.stats().await.packets_sent, sent);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), recv_packet(&server, 1))
+                .await
+                .is_err()
+        );
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_malformed_packets() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.auto_heartbeat(false).build().unwrap();
+        connect(&mut tracker, &server).await;
+        let port = tracker
+            .socket
+            .as_ref()
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let tracker_addr = SocketAddr::from(([127, 0, 0, 1], port));
+
+        // A heartbeat cut off in the middle of the header
+        for _ in 0..3 {
+            server
+                .send_to(&[0, 0, 0, 1, 0], tracker_addr)
+                .await
+                .unwrap();
+        }
+        // Still receiving afterwards
+        let mut info = cb_header(PACKET_SENSOR_INFO, 1);
+        info.extend_from_slice(&[0, 1]);
+        server.send_to(&info, tracker_addr).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !tracker.is_sensor_acknowledged(0).await {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Receive task stopped after malformed packets");
+        assert_eq!(tracker.stats().await.malformed_packets, 3);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_malformed_log_rate_limit() {
+        let (server, builder) = mock_server().await;
+        let clock = clock::MockClock::new();
+        let mut tracker = builder
+            .auto_heartbeat(false)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+        let logged_at = || async { tracker.state.lock().await.stats.last_malformed_log };
+
+        let malformed = [0, 0, 0, 1, 0];
+        tracker.handle_packet(&malformed).await.unwrap();
+        let first = logged_at().await.unwrap();
+        // Too soon for another warning
+        clock.advance(receive::MALFORMED_LOG_INTERVAL / 2);
+        tracker.handle_packet(&malformed).await.unwrap();
+        assert_eq!(logged_at().await, Some(first));
+
+        clock.advance(receive::MALFORMED_LOG_INTERVAL);
+        tracker.handle_packet(&malformed).await.unwrap();
+        assert_eq!(
+            logged_at().await,
+            Some(first + receive::MALFORMED_LOG_INTERVAL * 3 / 2)
+        );
+        assert_eq!(tracker.stats().await.malformed_packets, 3);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_add_sensor_with_id() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        tracker
+            .add_sensor_with_id(5, ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        assert!(tracker
+            .add_sensor_with_id(5, ImuType::Lsm6dsv, SensorStatus::Ok)
+            .await
+            .is_err());
+        // Automatic ids go around the taken ones
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        let ids = tracker
+            .add_sensors([
+                (ImuType::Bno085, SensorStatus::Ok),
+                (ImuType::Bno085, SensorStatus::Ok),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(tracker.connected_sensor_ids(), [5, 0, 1, 2]);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_timeout_check_interval() {
+        let (server, builder) = mock_server().await;
+        let clock = clock::MockClock::new();
+        let mut tracker = builder
+            .server_timeout_ms(10_000)
+            .timeout_check_interval(Duration::from_millis(10))
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+
+        // Noticed on the next check, not a whole timeout later
+        clock.advance(Duration::from_secs(11));
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while tracker.is_connected().await {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Timeout wasn't checked often enough");
+    }
+
+    #[tokio::test]
+    async fn test_concatenated_packets() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+
+        // Two pings in one datagram, both should get echoed back
+        let mut datagram = cb_header(10, 1);
+        datagram.extend_from_slice(&[1, 2, 3, 4]);
+        datagram.extend(cb_header(10, 2));
+        datagram.extend_from_slice(&[5, 6, 7, 8]);
+        tracker.handle_packet(&datagram).await.unwrap();
+
+        let first = recv_packet(&server, 10).await;
+        let second = recv_packet(&server, 10).await;
+        assert_eq!(first[12..16], [1, 2, 3, 4]);
+        assert_eq!(second[12..16], [5, 6, 7, 8]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_packet_ends_datagram() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+
+        // The payload of the unknown packet would fail to parse if it were read as the next packet
+        let mut datagram = cb_header(99, 1);
+        datagram.extend_from_slice(&[1, 2, 3]);
+        tracker.handle_packet(&datagram).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_incoming() {
+        use futures::StreamExt;
+
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        let mut incoming = Box::pin(tracker.incoming());
+
+        let mut datagram = cb_header(10, 1);
+        datagram.extend_from_slice(&[1, 2, 3, 4]);
+        tracker.handle_packet(&datagram).await.unwrap();
+        // The heartbeat from connect may still be on its way through
+        let mut packet = incoming.next().await;
+        if packet == Some(CbPacket::Heartbeat) {
+            packet = incoming.next().await;
+        }
+        assert_eq!(
+            packet,
+            Some(CbPacket::Ping {
+                challenge: [1, 2, 3, 4]
+            })
+        );
+
+        // A new stream takes over and the old one ends
+        let _newer = tracker.incoming();
+        assert_eq!(incoming.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_packet_negotiation() {
+        let mut response = cb_header(3, 0);
+        response.push(12);
+        response.extend_from_slice(b"Hey OVR =D 4");
+
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder
+            .packet_negotiation(PacketNegotiation::MinProtocolVersion(5))
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+        assert!(tracker.uses_modern_packets().await);
+        tracker.handle_packet(&response).await.unwrap();
+        assert_eq!(
+            tracker.server_info().await.unwrap().protocol_version,
+            Some(4)
+        );
+        assert!(!tracker.uses_modern_packets().await);
+        assert!(tracker.send_flex_data(0, 0.5).await.is_err());
+        tracker.deinit().await.unwrap();
+
+        // No cutoff unless one is configured
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        tracker.handle_packet(&response).await.unwrap();
+        assert!(tracker.send_flex_data(0, 0.5).await.is_ok());
+        tracker.deinit().await.unwrap();
+
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder
+            .packet_negotiation(PacketNegotiation::MinProtocolVersion(4))
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+        tracker.handle_packet(&response).await.unwrap();
+        assert!(tracker.uses_modern_packets().await);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_flex_data() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        let seq = tracker.send_flex_data(2, 0.75).await.unwrap();
+        // Sensor id then the value after the header
+        let packet = recv_packet(&server, PACKET_FLEX_DATA).await;
+        assert_eq!(packet.len(), 17);
+        assert_eq!(packet[4..12], seq.to_be_bytes());
+        assert_eq!(packet[12], 2);
+        assert_eq!(packet[13..17], 0.75f32.to_be_bytes());
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_position() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        assert!(tracker.send_position(0, (1.0, 2.0, 3.0)).await.is_err());
+        tracker.deinit().await.unwrap();
+
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.position_packet_type(200).build().unwrap();
+        connect(&mut tracker, &server).await;
+        let seq = tracker.send_position(0, (1.0, 2.0, 3.0)).await.unwrap();
+        let packet = recv_packet(&server, 200).await;
+        assert_eq!(u64::from_be_bytes(packet[4..12].try_into().unwrap()), seq);
+        assert_eq!(packet[12], 0);
+        assert_eq!(&packet[13..17], &1.0f32.to_be_bytes());
+        assert_eq!(&packet[21..25], &3.0f32.to_be_bytes());
+        assert_eq!(packet.len(), 25);
+        tracker.deinit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_command() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder.build().unwrap();
+        connect(&mut tracker, &server).await;
+        let mut events = tracker.subscribe_events();
+
+        let mut datagram = cb_header(PACKET_COMMAND, 1);
+        datagram.push(3);
+        tracker.handle_packet(&datagram).await.unwrap();
+
+        assert_eq!(
+            events.recv().await.unwrap(),
+            TrackerEvent::Command(ServerCommand::Blink)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reliable_sensor_info() {
+        let (server, builder) = mock_server().await;
+        let mut tracker = builder
+            .reliable_sensor_info(Duration::from_millis(50), 20)
+            .build()
+            .unwrap();
+        connect(&mut tracker, &server).await;
+
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+
+        // Original send plus at least one retry while unacknowledged
+        let first = recv_packet(&server, PACKET_SENSOR_INFO).await;
+        let retry = recv_packet(&server, PACKET_SENSOR_INFO).await;
+        assert_eq!(first[12], 0);
+        assert_eq!(retry[12], 0);
+        assert!(!tracker.is_sensor_acknowledged(0).await);
+
+        let mut ack = cb_header(PACKET_SENSOR_INFO, 1);
+        ack.extend_from_slice(&[0, 1]);
+        tracker.handle_packet(&ack).await.unwrap();
+        assert!(tracker.is_sensor_acknowledged(0).await);
+    }
+
+    #[tokio::test]
+    async fn test_all() {
+        use {sleep, Duration};
+
+        let mac_address = [0xDE, 0xAD, 0xBE, 0xEF, 0x01, 0x02];
+        let firmware_version = "tracker_emulation-rs test".to_string();
+
+        // Create tracker instance
+        let mut tracker = EmulatedTracker::builder()
+            .mac_address(mac_address)
+            .firmware_version(firmware_version)
+            .build()
+            .expect("Failed to create EmulatedTracker");
 
         tracker.init().await.expect("Failed to initialize tracker");
         sleep(Duration::from_secs(1)).await;
@@ -575,7 +4202,7 @@ mod tests {
             tracker
                 .add_sensor(ImuType::Mpu6050, SensorStatus::Ok)
                 .await
-                .expect(&format!("Failed to add sensor {}", i));
+                .unwrap_or_else(|e| panic!("Failed to add sensor {}: {}", i, e));
             sleep(Duration::from_millis(100)).await;
         }
 
@@ -619,7 +4246,7 @@ mod tests {
             tracker
                 .send_user_action(action)
                 .await
-                .expect(&format!("Failed to send user action: {:?}", action_type));
+                .unwrap_or_else(|e| panic!("Failed to send user action: {:?}: {}", action_type, e));
             sleep(Duration::from_secs(3)).await;
         }
 
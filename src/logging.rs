@@ -0,0 +1,169 @@
+// Logging macros used throughout the crate.
+// With the `tracing` feature these forward to the matching `tracing` macro, without it they compile
+// to nothing (the fields and format args are still evaluated by reference so nothing goes unused).
+
+#[cfg(feature = "tracing")]
+macro_rules! trace {
+    ($($arg:tt)*) => { ::tracing::trace!($($arg)*) };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! debug {
+    ($($arg:tt)*) => { ::tracing::debug!($($arg)*) };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! warn {
+    ($($arg:tt)*) => { ::tracing::warn!($($arg)*) };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! error {
+    ($($arg:tt)*) => { ::tracing::error!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace {
+    ($($arg:tt)*) => { discard!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! debug {
+    ($($arg:tt)*) => { discard!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! warn {
+    ($($arg:tt)*) => { discard!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! error {
+    ($($arg:tt)*) => { discard!($($arg)*) };
+}
+
+// Accepts the subset of the tracing syntax we use: `name = %val`, `name = ?val`, `name = val`,
+// followed by a format string and its args.
+#[cfg(not(feature = "tracing"))]
+macro_rules! discard {
+    () => {};
+    ($name:ident = %$val:expr $(, $($rest:tt)*)?) => {{
+        let _ = &$val;
+        $(discard!($($rest)*);)?
+    }};
+    ($name:ident = ?$val:expr $(, $($rest:tt)*)?) => {{
+        let _ = &$val;
+        $(discard!($($rest)*);)?
+    }};
+    ($name:ident = $val:expr $(, $($rest:tt)*)?) => {{
+        let _ = &$val;
+        $(discard!($($rest)*);)?
+    }};
+    ($fmt:literal $($args:tt)*) => {{
+        let _ = format_args!($fmt $($args)*);
+    }};
+}
+
+use std::collections::VecDeque;
+
+// Formats bytes as a classic hex dump (offset, 16 bytes of hex, then printable ASCII) for the debug logs, since
+// the protocol is binary and lossy UTF-8 tells you nothing. Only formatted if the log line is actually written.
+pub(crate) struct HexDump<'a>(pub(crate) &'a [u8]);
+
+impl std::fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (line, chunk) in self.0.chunks(16).enumerate() {
+            write!(f, "\n{:04x} ", line * 16)?;
+            for i in 0..16 {
+                match chunk.get(i) {
+                    Some(byte) => write!(f, " {byte:02x}")?,
+                    None => write!(f, "   ")?,
+                }
+            }
+            write!(f, "  |")?;
+            for &byte in chunk {
+                let c = if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                };
+                write!(f, "{c}")?;
+            }
+            write!(f, "|")?;
+        }
+        Ok(())
+    }
+}
+
+// The last few datagrams each way, kept for packet_history. A depth of 0 keeps nothing.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PacketHistory {
+    depth: usize,
+    sent: VecDeque<Vec<u8>>,
+    received: VecDeque<Vec<u8>>,
+}
+
+impl PacketHistory {
+    pub(crate) fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn push_sent(&mut self, bytes: &[u8]) {
+        push_bounded(&mut self.sent, self.depth, bytes);
+    }
+
+    pub(crate) fn push_received(&mut self, bytes: &[u8]) {
+        push_bounded(&mut self.received, self.depth, bytes);
+    }
+
+    pub(crate) fn sent(&self) -> Vec<Vec<u8>> {
+        self.sent.iter().cloned().collect()
+    }
+
+    pub(crate) fn received(&self) -> Vec<Vec<u8>> {
+        self.received.iter().cloned().collect()
+    }
+}
+
+fn push_bounded(buffer: &mut VecDeque<Vec<u8>>, depth: usize, bytes: &[u8]) {
+    if depth == 0 {
+        return;
+    }
+    if buffer.len() == depth {
+        buffer.pop_front();
+    }
+    buffer.push_back(bytes.to_vec());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_history() {
+        let mut history = PacketHistory::new(2);
+        for byte in 1..=3 {
+            history.push_sent(&[byte]);
+        }
+        assert_eq!(history.sent(), vec![vec![2], vec![3]]);
+        assert!(history.received().is_empty());
+
+        let mut disabled = PacketHistory::new(0);
+        disabled.push_received(&[1]);
+        assert!(disabled.received().is_empty());
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let dump = HexDump(b"\x00\x00\x00\x03hello, world!\xff\x10").to_string();
+        assert_eq!(
+            dump,
+            "\n0000  00 00 00 03 68 65 6c 6c 6f 2c 20 77 6f 72 6c 64  |....hello, world|\
+             \n0010  21 ff 10                                         |!..|"
+        );
+        assert_eq!(HexDump(&[]).to_string(), "");
+    }
+}
@@ -0,0 +1,72 @@
+use crate::TrackerError;
+
+// Parses a MAC address like "DE:AD:BE:EF:01:02" (dashes as separators work too)
+pub fn mac_from_str(mac: &str) -> Result<[u8; 6], TrackerError> {
+    let invalid = || TrackerError::InvalidMacAddress(mac.to_string());
+
+    let mut parts = mac.trim().split([':', '-']);
+    let mut mac_address = [0u8; 6];
+    for byte in mac_address.iter_mut() {
+        let part = parts.next().ok_or_else(invalid)?;
+        if part.len() != 2 {
+            return Err(invalid());
+        }
+        *byte = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+    }
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+
+    Ok(mac_address)
+}
+
+// The other way around, "DE:AD:BE:EF:01:02"
+pub fn mac_to_string(mac_address: [u8; 6]) -> String {
+    mac_address
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+// Random unicast MAC with the locally-administered bit set, so it can't clash with a real device
+pub fn random_mac() -> [u8; 6] {
+    let mut mac_address: [u8; 6] = rand::random();
+    mac_address[0] = (mac_address[0] | 0x02) & !0x01;
+    mac_address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mac_from_str() {
+        let expected = [0xDE, 0xAD, 0xBE, 0xEF, 0x01, 0x02];
+        assert_eq!(mac_from_str("DE:AD:BE:EF:01:02").unwrap(), expected);
+        assert_eq!(mac_from_str("de-ad-be-ef-01-02").unwrap(), expected);
+        assert_eq!(mac_to_string(expected), "DE:AD:BE:EF:01:02");
+
+        for invalid in [
+            "",
+            "DE:AD:BE:EF:01",
+            "DE:AD:BE:EF:01:02:03",
+            "DE:AD:BE:EF:1:02",
+            "GG:AD:BE:EF:01:02",
+        ] {
+            assert!(matches!(
+                mac_from_str(invalid),
+                Err(TrackerError::InvalidMacAddress(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_random_mac() {
+        for _ in 0..32 {
+            let mac_address = random_mac();
+            assert_eq!(mac_address[0] & 0x02, 0x02);
+            assert_eq!(mac_address[0] & 0x01, 0x00);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+// Small value types for the data the tracker sends
+
+use firmware_protocol::SlimeQuaternion;
+
+// Right-handed, same axes as the rotation quaternions. Acceleration is in m/s² in the sensor's own frame with
+// gravity already removed (like the firmware's linear acceleration), position is in metres.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<(f32, f32, f32)> for Vec3 {
+    fn from((x, y, z): (f32, f32, f32)) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<[f32; 3]> for Vec3 {
+    fn from([x, y, z]: [f32; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<Vec3> for (f32, f32, f32) {
+    fn from(vec: Vec3) -> Self {
+        (vec.x, vec.y, vec.z)
+    }
+}
+
+// Rotation with named components, since SlimeQuaternion's i/j/k/w order is easy to mix up with w/x/y/z. i, j and k
+// are x, y and z, and w is the real part.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat::from_xyzw(0.0, 0.0, 0.0, 1.0);
+
+    pub const fn identity() -> Self {
+        Self::IDENTITY
+    }
+
+    // Real part last, like SlimeQuaternion, glam and most game engines
+    pub const fn from_xyzw(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    // Real part first, like nalgebra and most maths papers
+    pub const fn from_wxyz(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    // The opposite rotation, for unit quaternions
+    pub fn conjugate(self) -> Self {
+        Self::from_xyzw(-self.x, -self.y, -self.z, self.w)
+    }
+
+    // Angle in radians of the rotation from self to other, for unit quaternions
+    pub fn angle_to(self, other: Quat) -> f32 {
+        let dot = self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w;
+        // q and -q are the same rotation
+        2.0 * dot.abs().min(1.0).acos()
+    }
+
+    pub fn rotate(self, vec: Vec3) -> Vec3 {
+        // v + 2w(u x v) + 2u x (u x v), with u the vector part
+        let u = Vec3::new(self.x, self.y, self.z);
+        let uv = cross(u, vec);
+        let uuv = cross(u, uv);
+        Vec3::new(
+            vec.x + 2.0 * (self.w * uv.x + uuv.x),
+            vec.y + 2.0 * (self.w * uv.y + uuv.y),
+            vec.z + 2.0 * (self.w * uv.z + uuv.z),
+        )
+    }
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    Vec3::new(
+        a.y * b.z - a.z * b.y,
+        a.z * b.x - a.x * b.z,
+        a.x * b.y - a.y * b.x,
+    )
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<Quat> for SlimeQuaternion {
+    fn from(quat: Quat) -> Self {
+        SlimeQuaternion {
+            i: quat.x,
+            j: quat.y,
+            k: quat.z,
+            w: quat.w,
+        }
+    }
+}
+
+impl From<SlimeQuaternion> for Quat {
+    fn from(quat: SlimeQuaternion) -> Self {
+        Self::from(&quat)
+    }
+}
+
+// SlimeQuaternion isn't Copy, this is how to keep a copy of one around
+impl From<&SlimeQuaternion> for Quat {
+    fn from(quat: &SlimeQuaternion) -> Self {
+        Self::from_xyzw(quat.i, quat.j, quat.k, quat.w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quat_order() {
+        let a = SlimeQuaternion::from(Quat::from_xyzw(0.1, 0.2, 0.3, 0.9));
+        let b = SlimeQuaternion::from(Quat::from_wxyz(0.9, 0.1, 0.2, 0.3));
+        assert_eq!(a, b);
+        assert_eq!((a.i, a.j, a.k, a.w), (0.1, 0.2, 0.3, 0.9));
+        assert_eq!(SlimeQuaternion::from(Quat::identity()).w, 1.0);
+    }
+
+    #[test]
+    fn test_quat_rotate() {
+        // 90 degrees around z turns x into y
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let quat = Quat::from_xyzw(0.0, 0.0, half, half);
+        let rotated = quat.rotate(Vec3::new(1.0, 0.0, 0.0));
+        assert!((rotated.x).abs() < 1e-6 && (rotated.y - 1.0).abs() < 1e-6);
+        let back = quat.conjugate().rotate(rotated);
+        assert!((back.x - 1.0).abs() < 1e-6 && back.y.abs() < 1e-6);
+        assert!((Quat::IDENTITY.angle_to(quat) - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+    }
+}
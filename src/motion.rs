@@ -0,0 +1,188 @@
+use crate::builder::rate_interval;
+use crate::{EmulatedTracker, TrackerError};
+use firmware_protocol::{SensorDataType, SlimeQuaternion};
+use std::f32::consts::TAU;
+use std::time::Duration;
+use tokio::time::{interval, Instant, MissedTickBehavior};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    fn unit(self) -> (f32, f32, f32) {
+        match self {
+            Axis::X => (1.0, 0.0, 0.0),
+            Axis::Y => (0.0, 1.0, 0.0),
+            Axis::Z => (0.0, 0.0, 1.0),
+        }
+    }
+}
+
+// Something that can describe a tracker's motion at `t` seconds since it started
+pub trait MotionGenerator {
+    fn rotation(&self, t: f32) -> SlimeQuaternion;
+
+    // Acceleration in the tracker's own frame (m/s²), stationary by default
+    fn acceleration(&self, _t: f32) -> (f32, f32, f32) {
+        (0.0, 0.0, 0.0)
+    }
+}
+
+// Swings back and forth around `axis`, amplitude is in radians and frequency in Hz
+#[derive(Debug, Clone, Copy)]
+pub struct SineRotation {
+    pub axis: Axis,
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+pub fn sine_rotation(axis: Axis, amplitude: f32, frequency: f32) -> SineRotation {
+    SineRotation {
+        axis,
+        amplitude,
+        frequency,
+    }
+}
+
+impl MotionGenerator for SineRotation {
+    fn rotation(&self, t: f32) -> SlimeQuaternion {
+        let angle = self.amplitude * (TAU * self.frequency * t).sin();
+        axis_angle(self.axis, angle)
+    }
+}
+
+// Moves around a horizontal circle (radius in metres, frequency in laps per second) while facing along it
+#[derive(Debug, Clone, Copy)]
+pub struct CircularMotion {
+    pub radius: f32,
+    pub frequency: f32,
+}
+
+pub fn circular_motion(radius: f32, frequency: f32) -> CircularMotion {
+    CircularMotion { radius, frequency }
+}
+
+impl MotionGenerator for CircularMotion {
+    fn rotation(&self, t: f32) -> SlimeQuaternion {
+        axis_angle(Axis::Y, TAU * self.frequency * t)
+    }
+
+    fn acceleration(&self, _t: f32) -> (f32, f32, f32) {
+        // Since the tracker turns with the circle, the centripetal acceleration always points the same
+        // way in its own frame
+        let angular_velocity = TAU * self.frequency;
+        (-self.radius * angular_velocity * angular_velocity, 0.0, 0.0)
+    }
+}
+
+fn axis_angle(axis: Axis, angle: f32) -> SlimeQuaternion {
+    let (x, y, z) = axis.unit();
+    let (sin, cos) = (angle / 2.0).sin_cos();
+    SlimeQuaternion {
+        i: x * sin,
+        j: y * sin,
+        k: z * sin,
+        w: cos,
+    }
+}
+
+// Sends rotation and acceleration from `generator` for `sensor_id` at `rate_hz` until `duration` has passed.
+// Fails straight away if `rate_hz` isn't a positive number.
+pub async fn drive(
+    tracker: &EmulatedTracker,
+    sensor_id: u8,
+    generator: &impl MotionGenerator,
+    rate_hz: f32,
+    duration: Duration,
+) -> Result<(), String> {
+    let mut ticker = interval(rate_interval(rate_hz)?);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    // A data_rate_hz below rate_hz just leaves some of the updates out
+    let throttled = TrackerError::Throttled(sensor_id).to_string();
+    let skip_throttled = |result: Result<u64, String>| match result {
+        Err(e) if e != throttled => Err(e),
+        _ => Ok(()),
+    };
+
+    let start = Instant::now();
+    loop {
+        ticker.tick().await;
+        let elapsed = start.elapsed();
+        if elapsed >= duration {
+            break;
+        }
+
+        let t = elapsed.as_secs_f32();
+        skip_throttled(
+            tracker
+                .send_rotation(sensor_id, SensorDataType::Normal, generator.rotation(t), 0)
+                .await,
+        )?;
+        skip_throttled(
+            tracker
+                .send_acceleration(sensor_id, generator.acceleration(t))
+                .await,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use firmware_protocol::{ImuType, SensorStatus};
+
+    #[test]
+    fn test_generators() {
+        let sine = sine_rotation(Axis::X, 1.0, 0.5);
+        let quat = sine.rotation(0.0);
+        assert_eq!((quat.i, quat.j, quat.k, quat.w), (0.0, 0.0, 0.0, 1.0));
+
+        // Peak of the swing after a quarter period
+        let quat = sine.rotation(0.5);
+        assert!((quat.i - 0.5f32.sin()).abs() < 1e-5);
+        assert!((quat.w - 0.5f32.cos()).abs() < 1e-5);
+
+        let circle = circular_motion(2.0, 1.0);
+        for t in [0.0, 0.3, 1.7] {
+            let quat = circle.rotation(t);
+            let norm = quat.i * quat.i + quat.j * quat.j + quat.k * quat.k + quat.w * quat.w;
+            assert!((norm - 1.0).abs() < 1e-5);
+        }
+        let (x, y, z) = circle.acceleration(0.0);
+        assert!((x + 2.0 * TAU * TAU).abs() < 1e-3);
+        assert_eq!((y, z), (0.0, 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_drive() {
+        let mut tracker = EmulatedTracker::builder()
+            .dry_run(true)
+            .data_rate_hz(20.0)
+            .build()
+            .unwrap();
+        tracker.init().await.unwrap();
+        tracker
+            .add_sensor(ImuType::Bno085, SensorStatus::Ok)
+            .await
+            .unwrap();
+        let generator = sine_rotation(Axis::X, 1.0, 0.5);
+        for rate_hz in [0.0, -1.0, f32::NAN, 1e30] {
+            assert_eq!(
+                drive(&tracker, 0, &generator, rate_hz, Duration::from_millis(100)).await,
+                Err(TrackerError::InvalidRate(rate_hz).to_string())
+            );
+        }
+        drive(&tracker, 0, &generator, 200.0, Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert!(tracker.stats().await.throttled_packets > 0);
+        tracker.deinit().await.unwrap();
+    }
+}
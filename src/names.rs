@@ -0,0 +1,140 @@
+use crate::TrackerError;
+use firmware_protocol::deku::bitvec::{BitVec, Msb0};
+use firmware_protocol::deku::ctx::Endian;
+use firmware_protocol::deku::prelude::*;
+use firmware_protocol::{BoardType, ImuType, McuType};
+use std::fmt::Debug;
+
+// Hardware names from config files and CLIs, e.g. "Bno085", "bno-085" or just the protocol id ("4").
+// Names are matched against the variant names without caring about case, dashes, underscores or spaces. The
+// protocol types don't implement FromStr (and can't from here), so these are plain functions like mac_from_str.
+
+pub fn imu_from_name(name: &str) -> Result<ImuType, TrackerError> {
+    from_name(name, 1)
+}
+
+pub fn board_from_name(name: &str) -> Result<BoardType, TrackerError> {
+    from_name(name, 2)
+}
+
+pub fn mcu_from_name(name: &str) -> Result<McuType, TrackerError> {
+    from_name(name, 2)
+}
+
+// Tries every id the type could have and compares the debug name, so nothing has to be updated when
+// firmware_protocol adds hardware. All the known ids are below 256 even for the u16 types.
+fn from_name<T>(name: &str, id_len: usize) -> Result<T, TrackerError>
+where
+    T: for<'a> DekuRead<'a, Endian> + Debug,
+{
+    let unknown = || TrackerError::UnknownHardwareName(name.to_string());
+
+    if let Ok(id) = name.trim().parse::<u16>() {
+        if id_len == 1 && id > u8::MAX as u16 {
+            return Err(unknown());
+        }
+        return read_id(id, id_len).ok_or_else(unknown);
+    }
+
+    let wanted = normalize(name);
+    (0..=u8::MAX as u16)
+        .filter_map(|id| read_id::<T>(id, id_len))
+        .find(|value| normalize(&format!("{value:?}")) == wanted)
+        .ok_or_else(unknown)
+}
+
+fn read_id<T>(id: u16, id_len: usize) -> Option<T>
+where
+    T: for<'a> DekuRead<'a, Endian>,
+{
+    let bytes = id.to_be_bytes();
+    let bits = BitVec::<u8, Msb0>::from_slice(&bytes[bytes.len() - id_len..]);
+    T::read(&bits, Endian::Big).ok().map(|(_, value)| value)
+}
+
+// The hardware enums don't implement Clone, but on the wire they're just an id. The tracker keeps that id (which is
+// Copy) and turns it back into the enum whenever a packet or a config snapshot needs one.
+pub(crate) trait HardwareId:
+    Sized + DekuWrite<Endian> + for<'a> DekuRead<'a, Endian>
+{
+    type Id: Copy + Default + Into<u16> + for<'a> DekuRead<'a, Endian>;
+
+    fn unknown(id: Self::Id) -> Self;
+
+    // Writing one of these can't fail, the default (0, what unknown hardware is sent as anyway) only keeps this
+    // from panicking if it ever did
+    fn id(&self) -> Self::Id {
+        let mut bits = BitVec::new();
+        if self.write(&mut bits, Endian::Big).is_err() {
+            return Self::Id::default();
+        }
+        Self::Id::read(&bits, Endian::Big)
+            .map(|(_, id)| id)
+            .unwrap_or_default()
+    }
+
+    fn from_id(id: Self::Id) -> Self {
+        read_id(id.into(), std::mem::size_of::<Self::Id>()).unwrap_or_else(|| Self::unknown(id))
+    }
+}
+
+impl HardwareId for BoardType {
+    type Id = u16;
+
+    fn unknown(id: u16) -> Self {
+        BoardType::Unknown(id)
+    }
+}
+
+impl HardwareId for McuType {
+    type Id = u16;
+
+    fn unknown(id: u16) -> Self {
+        McuType::Unknown(id)
+    }
+}
+
+impl HardwareId for ImuType {
+    type Id = u8;
+
+    fn unknown(id: u8) -> Self {
+        ImuType::Unknown(id)
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(imu_from_name("Bno085").unwrap(), ImuType::Bno085);
+        assert_eq!(imu_from_name("BNO-085").unwrap(), ImuType::Bno085);
+        assert_eq!(imu_from_name("4").unwrap(), ImuType::Bno085);
+        assert_eq!(board_from_name("slimevr").unwrap(), BoardType::SlimeVR);
+        assert_eq!(mcu_from_name("esp32_c3").unwrap(), McuType::Esp32C3);
+
+        assert!(imu_from_name("not an imu").is_err());
+        assert!(imu_from_name("300").is_err());
+    }
+
+    #[test]
+    fn test_hardware_id() {
+        assert_eq!(ImuType::Bno085.id(), 4);
+        assert_eq!(ImuType::from_id(ImuType::Lsm6dsv.id()), ImuType::Lsm6dsv);
+        assert_eq!(ImuType::from_id(200), ImuType::Unknown(200));
+        assert_eq!(
+            BoardType::from_id(BoardType::SlimeVR.id()),
+            BoardType::SlimeVR
+        );
+        assert_eq!(McuType::from_id(McuType::Esp32C3.id()), McuType::Esp32C3);
+        assert_eq!(McuType::Unknown(300).id(), 300);
+    }
+}
@@ -0,0 +1,47 @@
+use std::net::Ipv4Addr;
+
+// Broadcast address of the subnet `addr` is on, e.g. 192.168.1.20/24 gives 192.168.1.255
+pub fn subnet_broadcast(addr: Ipv4Addr, prefix_len: u8) -> Ipv4Addr {
+    let host_mask = u32::MAX.checked_shr(prefix_len.min(32) as u32).unwrap_or(0);
+    Ipv4Addr::from(u32::from(addr) | host_mask)
+}
+
+// Either the limited broadcast address or one that could be a subnet broadcast, meaning the host part is all ones
+// for some subnet between /8 and /30. Without the netmask that's as much as can be checked.
+#[cfg(feature = "tokio")]
+pub(crate) fn is_broadcast_address(addr: Ipv4Addr) -> bool {
+    if addr.is_broadcast() {
+        return true;
+    }
+    let host_bits = u32::from(addr).trailing_ones();
+    !addr.is_loopback() && !addr.is_multicast() && (2..=24).contains(&host_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_broadcast_addresses() {
+        let addr = Ipv4Addr::new(192, 168, 1, 20);
+        assert_eq!(subnet_broadcast(addr, 24), Ipv4Addr::new(192, 168, 1, 255));
+        assert_eq!(
+            subnet_broadcast(addr, 16),
+            Ipv4Addr::new(192, 168, 255, 255)
+        );
+        assert_eq!(subnet_broadcast(addr, 32), addr);
+        assert_eq!(subnet_broadcast(addr, 0), Ipv4Addr::BROADCAST);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_is_broadcast_address() {
+        let addr = Ipv4Addr::new(192, 168, 1, 20);
+        assert!(is_broadcast_address(Ipv4Addr::BROADCAST));
+        assert!(is_broadcast_address(Ipv4Addr::new(192, 168, 1, 255)));
+        assert!(is_broadcast_address(Ipv4Addr::new(10, 0, 0, 127)));
+        assert!(!is_broadcast_address(addr));
+        assert!(!is_broadcast_address(Ipv4Addr::UNSPECIFIED));
+        assert!(!is_broadcast_address(Ipv4Addr::new(127, 255, 255, 255)));
+    }
+}
@@ -0,0 +1,103 @@
+// Random noise on top of clean motion, so test data looks more like what real IMUs send
+
+use crate::{EmulatedTracker, Vec3};
+use firmware_protocol::{SensorDataType, SlimeQuaternion};
+use std::f32::consts::TAU;
+
+// Standard deviations of the noise, rotation in radians and acceleration in m/s² (per axis)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NoiseConfig {
+    pub rotation_std_dev: f32,
+    pub acceleration_std_dev: f32,
+}
+
+// Rotates `quat` a little further around a random axis, the angle being normally distributed
+pub fn perturb_rotation(quat: SlimeQuaternion, std_dev: f32) -> SlimeQuaternion {
+    if std_dev <= 0.0 {
+        return quat;
+    }
+
+    // A normally distributed vector points in a uniformly random direction
+    let axis = Vec3::new(gaussian(), gaussian(), gaussian());
+    let len = (axis.x * axis.x + axis.y * axis.y + axis.z * axis.z).sqrt();
+    if len == 0.0 {
+        return quat;
+    }
+    let (sin, cos) = (gaussian() * std_dev / 2.0).sin_cos();
+    let (x, y, z) = (axis.x / len * sin, axis.y / len * sin, axis.z / len * sin);
+
+    // noise * quat, then normalized again so float error doesn't build up
+    let SlimeQuaternion { i, j, k, w } = quat;
+    let result = [
+        cos * i + x * w + y * k - z * j,
+        cos * j - x * k + y * w + z * i,
+        cos * k + x * j - y * i + z * w,
+        cos * w - x * i - y * j - z * k,
+    ];
+    let norm = result.iter().map(|v| v * v).sum::<f32>().sqrt();
+    SlimeQuaternion {
+        i: result[0] / norm,
+        j: result[1] / norm,
+        k: result[2] / norm,
+        w: result[3] / norm,
+    }
+}
+
+pub fn perturb_acceleration(acceleration: impl Into<Vec3>, std_dev: f32) -> Vec3 {
+    let acceleration = acceleration.into();
+    if std_dev <= 0.0 {
+        return acceleration;
+    }
+    Vec3::new(
+        acceleration.x + gaussian() * std_dev,
+        acceleration.y + gaussian() * std_dev,
+        acceleration.z + gaussian() * std_dev,
+    )
+}
+
+// Standard normal sample (Box-Muller)
+fn gaussian() -> f32 {
+    let u1 = 1.0 - rand::random::<f32>();
+    let u2 = rand::random::<f32>();
+    (-2.0 * u1.ln()).sqrt() * (TAU * u2).cos()
+}
+
+impl EmulatedTracker {
+    // send_rotation with noise from `noise` added to `rotation_data` first
+    pub async fn send_rotation_noisy(
+        &self,
+        sensor_id: u8,
+        rotation_data: SlimeQuaternion,
+        accuracy: u8,
+        noise: &NoiseConfig,
+    ) -> Result<u64, String> {
+        let rotation_data = perturb_rotation(rotation_data, noise.rotation_std_dev);
+        self.send_rotation(sensor_id, SensorDataType::Normal, rotation_data, accuracy)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perturb() {
+        let identity = || SlimeQuaternion {
+            i: 0.0,
+            j: 0.0,
+            k: 0.0,
+            w: 1.0,
+        };
+        let quat = perturb_rotation(identity(), 0.0);
+        assert_eq!((quat.i, quat.j, quat.k, quat.w), (0.0, 0.0, 0.0, 1.0));
+        assert_eq!(perturb_acceleration(Vec3::ZERO, 0.0), Vec3::ZERO);
+
+        for _ in 0..100 {
+            let SlimeQuaternion { i, j, k, w } = perturb_rotation(identity(), 0.05);
+            assert!(((i * i + j * j + k * k + w * w).sqrt() - 1.0).abs() < 1e-5);
+            // Angle is 2 * acos(w), more than 10 standard deviations out would be a bug
+            assert!(2.0 * w.abs().min(1.0).acos() < 0.5);
+        }
+    }
+}
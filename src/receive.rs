@@ -0,0 +1,330 @@
+// Handling of everything the server sends us. Runs in a background task for as long as the tracker is
+// initialized, so it only holds clones of the tracker's shared state (same as the heartbeat task).
+
+use crate::logging::HexDump;
+use crate::sans_io::{decode, Incoming};
+use crate::{
+    Clock, IncomingSender, SendPath, ServerInfo, ServerSelection, TrackerEvent, TrackerState,
+    TrackerStatus,
+};
+use firmware_protocol::{CbPacket, SbPacket};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::net::UdpSocket;
+use tokio::sync::watch::{Receiver, Sender};
+use tokio::sync::{broadcast, mpsc, Mutex, Notify};
+
+pub(crate) const MALFORMED_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub(crate) struct PacketHandler {
+    pub(crate) socket: Arc<UdpSocket>,
+    pub(crate) data_port: Option<u16>,
+    pub(crate) server_selection: ServerSelection,
+    pub(crate) auto_heartbeat: bool,
+    #[cfg(feature = "testing")]
+    pub(crate) freeze_heartbeat_numbering: bool,
+    pub(crate) state: Arc<Mutex<TrackerState>>,
+    // Replies go out the same way as everything else the tracker sends
+    pub(crate) send_path: SendPath,
+    pub(crate) status_tx: Arc<Sender<TrackerStatus>>,
+    pub(crate) event_tx: broadcast::Sender<TrackerEvent>,
+    pub(crate) incoming_tx: IncomingSender,
+    pub(crate) connected_notify: Arc<Notify>,
+    pub(crate) debug: Arc<AtomicBool>,
+    pub(crate) clock: Arc<dyn Clock>,
+}
+
+impl PacketHandler {
+    // Receives until the tracker stops (deinit or a heartbeat timeout), nothing the server sends ends it early
+    pub(crate) async fn run(self, mut status_rx: Receiver<TrackerStatus>, recv_buffer_size: usize) {
+        let mut buf = vec![0u8; recv_buffer_size];
+        loop {
+            if status_rx.borrow_and_update().is_stopped() {
+                break;
+            }
+
+            tokio::select! {
+                result = self.socket.recv_from(&mut buf) => match result {
+                    Ok((size, addr)) => self.handle_datagram(&buf[..size], buf.len(), addr).await,
+                    Err(e) => {
+                        error!(error = %e, "Failed to receive data");
+                    }
+                },
+                changed = status_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle_datagram(&self, data: &[u8], buf_len: usize, addr: SocketAddr) {
+        // Anything that didn't fit was dropped by the OS, so this packet is probably cut off
+        if data.len() == buf_len {
+            warn!(
+                peer = %addr,
+                size = data.len(),
+                "Datagram filled the receive buffer and may be truncated, consider raising recv_buffer_size"
+            );
+        }
+        if self.is_debug() {
+            trace!(
+                peer = %addr,
+                size = data.len(),
+                data = %HexDump(data),
+                "Received datagram"
+            );
+        }
+
+        let mut state = self.state.lock().await;
+        state.history.push_received(data);
+        let discovered = !state
+            .discovered_servers
+            .iter()
+            .any(|server| server.ip() == addr.ip());
+        if discovered {
+            state.discovered_servers.push(addr);
+            let _ = self.event_tx.send(TrackerEvent::ServersDiscovered(
+                state.discovered_servers.clone(),
+            ));
+        }
+        // Stick to the server we picked, other servers on the network can keep talking but aren't listened to
+        let ignored = match state.server_addr {
+            Some(server_addr) => server_addr.ip() != addr.ip(),
+            None => !self.server_selection.accepts(addr),
+        };
+        if ignored {
+            if self.is_debug() {
+                trace!(peer = %addr, "Ignoring datagram from another server");
+            }
+            if discovered {
+                state.publish();
+            }
+            return;
+        }
+
+        state.stats.packets_received += 1;
+        if state.status != TrackerStatus::Connected {
+            state.set_status(TrackerStatus::Connected, &self.status_tx);
+            state.server_addr = Some(SocketAddr::new(
+                addr.ip(),
+                self.data_port.unwrap_or(addr.port()),
+            ));
+            state.stats.connected_at = Some(self.clock.now());
+            // The watchdog counts from here until the first heartbeat arrives
+            state.stats.last_heartbeat_received = Some(self.clock.now());
+            self.connected_notify.notify_waiters();
+        }
+        state.last_received_packet_time = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u16;
+        drop(state);
+
+        if let Err(e) = self.handle_packet(data).await {
+            warn!(peer = %addr, error = %e, "Error handling packet");
+        }
+        // Once for the whole datagram, with whatever was in it already handled
+        self.state.lock().await.publish();
+    }
+
+    // A datagram can hold several packets back to back, so keep going until it's used up
+    pub(crate) async fn handle_packet(&self, data: &[u8]) -> Result<(), String> {
+        for incoming in decode(data) {
+            let incoming = match incoming {
+                Ok(incoming) => incoming,
+                // Anything before it in the datagram was still handled
+                Err(e) => {
+                    self.record_malformed(&e).await;
+                    break;
+                }
+            };
+            match incoming {
+                Incoming::SensorAck(sensor_id) => {
+                    if self.is_debug() {
+                        debug!(
+                            sensor_id = sensor_id,
+                            packet_type = "SensorInfo",
+                            "Server acknowledged sensor"
+                        );
+                    }
+                    self.state.lock().await.acked_sensors.insert(sensor_id);
+                }
+                Incoming::Command(command) => {
+                    if self.is_debug() {
+                        debug!(
+                            command = ?command,
+                            packet_type = "Command",
+                            "Received command from server"
+                        );
+                    }
+                    // Nobody listening is fine
+                    let _ = self.event_tx.send(TrackerEvent::Command(command));
+                }
+                Incoming::Packet { seq, packet } => {
+                    self.handle_cb_packet(seq, &packet).await?;
+                    self.forward_incoming(packet);
+                }
+                Incoming::Unknown {
+                    seq,
+                    packet_type,
+                    skipped,
+                } => {
+                    warn!(
+                        seq = seq,
+                        packet_type = packet_type,
+                        skipped = skipped,
+                        "Received unknown packet, skipping the rest of the datagram"
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Garbage from the server is counted, but only logged every so often so a server sending a lot of it can't flood
+    // the log. Never stops the receive task.
+    async fn record_malformed(&self, error: &str) {
+        let mut state = self.state.lock().await;
+        state.stats.malformed_packets += 1;
+        let now = self.clock.now();
+        if state
+            .stats
+            .last_malformed_log
+            .is_some_and(|logged| now.saturating_duration_since(logged) < MALFORMED_LOG_INTERVAL)
+        {
+            return;
+        }
+        state.stats.last_malformed_log = Some(now);
+        warn!(
+            error = %error,
+            malformed_packets = state.stats.malformed_packets,
+            "Received malformed packet, more are only logged every {}s",
+            MALFORMED_LOG_INTERVAL.as_secs()
+        );
+    }
+
+    // Hands the packet to the incoming stream if there is one
+    fn forward_incoming(&self, packet: CbPacket) {
+        let mut incoming_tx = self.incoming_tx.lock().unwrap();
+        let Some(tx) = incoming_tx.as_ref() else {
+            return;
+        };
+        match tx.try_send(packet) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(packet)) => {
+                if self.is_debug() {
+                    debug!(packet = ?packet, "Incoming stream is full, dropping packet");
+                }
+            }
+            // Stream was dropped
+            Err(mpsc::error::TrySendError::Closed(_)) => *incoming_tx = None,
+        }
+    }
+
+    async fn handle_cb_packet(&self, seq: u64, packet_data: &CbPacket) -> Result<(), String> {
+        match packet_data {
+            CbPacket::Heartbeat => {
+                if self.is_debug() {
+                    debug!(
+                        seq = seq,
+                        packet_type = "Heartbeat",
+                        "Received Heartbeat packet"
+                    );
+                }
+                {
+                    let mut state = self.state.lock().await;
+                    let now = self.clock.now();
+                    state.stats.last_heartbeat_received = Some(now);
+                    if let Some(sent) = state.stats.last_heartbeat_sent.take() {
+                        state.stats.last_heartbeat_rtt = Some(now.saturating_duration_since(sent));
+                    }
+                }
+                if self.auto_heartbeat {
+                    #[cfg(feature = "testing")]
+                    let frozen = self.freeze_heartbeat_numbering;
+                    #[cfg(not(feature = "testing"))]
+                    let frozen = false;
+                    self.send_path.send_heartbeat(&self.socket, frozen).await?;
+                }
+            }
+            CbPacket::Ping { challenge } => {
+                let challenge = *challenge;
+                if self.is_debug() {
+                    debug!(
+                        seq = seq,
+                        packet_type = "Ping",
+                        challenge = ?challenge,
+                        "Received Ping packet"
+                    );
+                }
+                let received_at = self.clock.now();
+
+                // Echo of a ping we initiated, so just record the round trip
+                {
+                    let mut state = self.state.lock().await;
+                    if let Some((pending, sent_at)) = state.stats.pending_ping {
+                        if pending == challenge {
+                            let rtt = received_at.saturating_duration_since(sent_at);
+                            state.stats.pending_ping = None;
+                            state.stats.last_ping_rtt = Some(rtt);
+                            state.stats.ping_rtt_total += rtt;
+                            state.stats.ping_rtt_samples += 1;
+                            return Ok(());
+                        }
+                    }
+                }
+
+                self.send(SbPacket::Ping { challenge }).await?;
+
+                let mut state = self.state.lock().await;
+                state.stats.last_ping_response_time =
+                    Some(self.clock.now().saturating_duration_since(received_at));
+            }
+            // Only ever sent by the server, trackers announce themselves with the handshake
+            CbPacket::Discovery => {
+                if self.is_debug() {
+                    trace!(
+                        seq = seq,
+                        packet_type = "Discovery",
+                        "Received Discovery packet"
+                    );
+                }
+            }
+            CbPacket::HandshakeResponse { version } => {
+                let version = version.to_string();
+                if self.is_debug() {
+                    trace!(
+                        seq = seq,
+                        packet_type = "HandshakeResponse",
+                        version = %version,
+                        "Received HandshakeResponse packet"
+                    );
+                }
+                self.state.lock().await.server_info = Some(ServerInfo::new(version.clone()));
+                // Nobody listening is fine
+                let _ = self
+                    .event_tx
+                    .send(TrackerEvent::HandshakeComplete { version });
+            }
+            _ => {
+                warn!(seq = seq, packet = ?packet_data, "Received unknown packet");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn send(&self, data: SbPacket) -> Result<u64, String> {
+        self.send_path.send_packet(&self.socket, data).await
+    }
+
+    fn is_debug(&self) -> bool {
+        self.debug.load(Ordering::Relaxed)
+    }
+}
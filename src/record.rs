@@ -0,0 +1,110 @@
+use crate::EmulatedTracker;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+// Recording format, repeated for every packet:
+// u64 microseconds since the recording started, u32 packet length, then the serialized packet (all big endian)
+
+pub struct Recorder {
+    start: Instant,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self {
+            start: Instant::now(),
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    pub(crate) fn record(&self, packet: &[u8]) -> io::Result<()> {
+        let offset = self.start.elapsed().as_micros() as u64;
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(&offset.to_be_bytes())?;
+        writer.write_all(&(packet.len() as u32).to_be_bytes())?;
+        writer.write_all(packet)
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+}
+
+pub struct RecordedPacket {
+    pub offset: Duration,
+    pub data: Vec<u8>,
+}
+
+pub fn read_recording(path: impl AsRef<Path>) -> io::Result<Vec<RecordedPacket>> {
+    let data = std::fs::read(path)?;
+    let truncated = || io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated recording");
+
+    let mut packets = Vec::new();
+    let mut rest = data.as_slice();
+    while !rest.is_empty() {
+        if rest.len() < 12 {
+            return Err(truncated());
+        }
+        let offset = u64::from_be_bytes(rest[..8].try_into().unwrap());
+        let len = u32::from_be_bytes(rest[8..12].try_into().unwrap()) as usize;
+        rest = &rest[12..];
+        if rest.len() < len {
+            return Err(truncated());
+        }
+        packets.push(RecordedPacket {
+            offset: Duration::from_micros(offset),
+            data: rest[..len].to_vec(),
+        });
+        rest = &rest[len..];
+    }
+
+    Ok(packets)
+}
+
+// Re-sends a recording through `tracker`, keeping the original spacing between packets.
+// Packets get fresh sequence numbers so the server doesn't drop them as old.
+pub async fn replay(path: impl AsRef<Path>, tracker: &EmulatedTracker) -> Result<(), String> {
+    let packets = read_recording(path).map_err(|e| format!("Failed to read recording: {e}"))?;
+
+    let start = Instant::now();
+    for packet in packets {
+        if let Some(wait) = packet.offset.checked_sub(start.elapsed()) {
+            sleep(wait).await;
+        }
+        tracker.send_renumbered(&packet.data).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_roundtrip() {
+        let path =
+            std::env::temp_dir().join(format!("tracker_emulation-rs-{}.rec", std::process::id()));
+
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record(&[1, 2, 3]).unwrap();
+        recorder.record(&[]).unwrap();
+        recorder.record(&[4; 20]).unwrap();
+        drop(recorder);
+
+        let packets = read_recording(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0].data, vec![1, 2, 3]);
+        assert!(packets[1].data.is_empty());
+        assert_eq!(packets[2].data, vec![4; 20]);
+        assert!(packets[0].offset <= packets[2].offset);
+    }
+}
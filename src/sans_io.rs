@@ -0,0 +1,436 @@
+// The protocol without any networking: builds the datagrams a tracker sends and makes sense of the ones the server
+// sends back, bytes in and bytes out. EmulatedTracker is one driver over this (a tokio socket plus the background
+// tasks), anything else that can move UDP datagrams around (WASM, embedded, a custom event loop) can use
+// TrackerCore directly. Nothing in here needs tokio, only firmware_protocol.
+//
+// There's no timing in here either: the driver decides when to send heartbeats and handshakes, and how long to wait
+// for the server before giving up.
+
+use crate::event::ServerCommand;
+use crate::math::Vec3;
+use crate::names::HardwareId;
+use firmware_protocol::deku::prelude::*;
+use firmware_protocol::{
+    BoardType, CbPacket, ImuType, McuType, Packet, SbPacket, SensorDataType, SensorStatus,
+    SlimeQuaternion,
+};
+
+// Packet types firmware_protocol doesn't cover yet, see RawPacket
+pub(crate) const PACKET_COMMAND: u32 = 4;
+pub(crate) const PACKET_SENSOR_INFO: u32 = 15;
+pub(crate) const PACKET_FLEX_DATA: u32 = 26;
+pub(crate) const PACKET_BUNDLE: u32 = 100;
+// Rotation before sensor ids existed, still what owoTrack sends
+pub(crate) const PACKET_ROTATION_LEGACY: u32 = 1;
+pub(crate) const PACKET_ACCELERATION: u32 = 4;
+// Covered by firmware_protocol, only here to tell already serialized packets apart
+#[cfg(feature = "tokio")]
+pub(crate) const PACKET_ROTATION_DATA: u32 = 17;
+
+// Packet type (u32) then sequence number (u64)
+const HEADER_LEN: usize = 12;
+
+// What the tracker tells the server about itself
+#[derive(Debug)]
+pub struct Handshake {
+    pub board: BoardType,
+    pub imu: ImuType,
+    pub mcu: McuType,
+    // Defaults to zeros like real firmware
+    pub imu_info: (u32, u32, u32),
+    pub build: u32,
+    pub firmware: String,
+    pub mac_address: [u8; 6],
+    // Leaves the MAC address out like firmware from before sensor ids did
+    pub legacy: bool,
+}
+
+impl Handshake {
+    // Handshakes always use sequence number 0
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let data = SbPacket::Handshake {
+            board: BoardType::from_id(self.board.id()),
+            imu: ImuType::from_id(self.imu.id()),
+            mcu: McuType::from_id(self.mcu.id()),
+            imu_info: self.imu_info,
+            build: self.build,
+            firmware: self.firmware.clone().into(),
+            mac_address: self.mac_address,
+        };
+        let mut bytes = encode(0, data)?;
+        // The MAC address is the last field
+        if self.legacy {
+            bytes.truncate(bytes.len() - 6);
+        }
+        Ok(bytes)
+    }
+}
+
+pub fn encode(packet_number: u64, data: SbPacket) -> Result<Vec<u8>, String> {
+    Packet::new(packet_number, data)
+        .to_bytes()
+        .map_err(|e| format!("Failed to serialize packet: {e}"))
+}
+
+// A packet firmware_protocol doesn't have yet, the header (packet type and sequence number) gets built by hand
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawPacket {
+    pub packet_type: u32,
+    pub payload: Vec<u8>,
+}
+
+impl RawPacket {
+    pub fn new(packet_type: u32, payload: Vec<u8>) -> Self {
+        Self {
+            packet_type,
+            payload,
+        }
+    }
+
+    // Value of a flex (resistance) sensor, like the ones on gloves
+    pub fn flex_data(sensor_id: u8, value: f32) -> Self {
+        let mut payload = vec![sensor_id];
+        payload.extend_from_slice(&value.to_be_bytes());
+        Self::new(PACKET_FLEX_DATA, payload)
+    }
+
+    // Sensor id then x, y, z. The protocol has no position packet yet, so the packet type has to come from whatever
+    // server this is meant for.
+    pub fn position(packet_type: u32, sensor_id: u8, position: impl Into<Vec3>) -> Self {
+        let Vec3 { x, y, z } = position.into();
+        let mut payload = vec![sensor_id];
+        payload.extend_from_slice(&floats(&[x, y, z]));
+        Self::new(packet_type, payload)
+    }
+
+    // Rotation without a sensor id, always sensor 0
+    pub fn legacy_rotation(rotation: &SlimeQuaternion) -> Self {
+        let &SlimeQuaternion { i, j, k, w } = rotation;
+        Self::new(PACKET_ROTATION_LEGACY, floats(&[i, j, k, w]))
+    }
+
+    // Same packet as the normal acceleration, it just ended after the vector before sensor ids
+    pub fn legacy_acceleration(acceleration: impl Into<Vec3>) -> Self {
+        let Vec3 { x, y, z } = acceleration.into();
+        Self::new(PACKET_ACCELERATION, floats(&[x, y, z]))
+    }
+
+    // Several packets in one datagram, each one prefixed with its length (u16) and without its own sequence number.
+    // The server has to support them.
+    pub fn bundle(packets: impl IntoIterator<Item = SbPacket>) -> Result<Self, String> {
+        let mut payload = Vec::new();
+        for data in packets {
+            payload.extend_from_slice(&bundle_entry(data)?);
+        }
+        Ok(Self::new(PACKET_BUNDLE, payload))
+    }
+
+    // Same as bundle, but split into as many bundles as it takes for none of the datagrams to be bigger than
+    // `max_size` bytes, each one with how many packets went into it. A packet too big to fit even on its own still
+    // gets a bundle of its own.
+    pub fn bundles(
+        packets: impl IntoIterator<Item = SbPacket>,
+        max_size: usize,
+    ) -> Result<Vec<(Self, usize)>, String> {
+        let mut bundles = Vec::new();
+        let mut payload = Vec::new();
+        let mut count = 0;
+        for data in packets {
+            let entry = bundle_entry(data)?;
+            if count > 0 && HEADER_LEN + payload.len() + entry.len() > max_size {
+                bundles.push((
+                    Self::new(PACKET_BUNDLE, std::mem::take(&mut payload)),
+                    count,
+                ));
+                count = 0;
+            }
+            payload.extend_from_slice(&entry);
+            count += 1;
+        }
+        if count > 0 {
+            bundles.push((Self::new(PACKET_BUNDLE, payload), count));
+        }
+        Ok(bundles)
+    }
+
+    pub fn to_bytes(&self, packet_number: u64) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        bytes.extend_from_slice(&self.packet_type.to_be_bytes());
+        bytes.extend_from_slice(&packet_number.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+}
+
+// A packet the way it goes in a bundle: its length (u16), then the packet without its sequence number, the bundle has
+// the only one
+fn bundle_entry(data: SbPacket) -> Result<Vec<u8>, String> {
+    let bytes = encode(0, data)?;
+    let len = (bytes.len() - 8) as u16;
+    let mut entry = Vec::with_capacity(2 + len as usize);
+    entry.extend_from_slice(&len.to_be_bytes());
+    entry.extend_from_slice(&bytes[..4]);
+    entry.extend_from_slice(&bytes[HEADER_LEN..]);
+    Ok(entry)
+}
+
+fn floats(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .collect()
+}
+
+// One thing the server sent, a datagram can hold several
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Incoming {
+    // Server confirming a sensor
+    SensorAck(u8),
+    Command(ServerCommand),
+    Packet {
+        seq: u64,
+        packet: CbPacket,
+    },
+    // Nothing says how long an unknown packet is, so whatever follows is its payload as far as we can tell and
+    // decoding stops here. `skipped` is how many bytes came after the header.
+    Unknown {
+        seq: u64,
+        packet_type: u32,
+        skipped: usize,
+    },
+}
+
+// Walks the packets in a datagram, see decode
+pub struct Decoder<'a> {
+    rest: &'a [u8],
+}
+
+pub fn decode(datagram: &[u8]) -> Decoder<'_> {
+    Decoder { rest: datagram }
+}
+
+impl Iterator for Decoder<'_> {
+    type Item = Result<Incoming, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.rest;
+        if rest.is_empty() {
+            return None;
+        }
+
+        // Server confirming a sensor: sensor id and status after the header. Nothing gives its length, so it's taken to
+        // be exactly the 14 bytes the SlimeVR server sends, which lets packets after it in the same datagram decode.
+        // An ack with more fields than that would have them read as the start of the next packet, most likely
+        // failing to parse and ending the datagram there.
+        if rest.len() >= 14 && rest[..4] == PACKET_SENSOR_INFO.to_be_bytes() {
+            self.rest = &rest[14..];
+            return Some(Ok(Incoming::SensorAck(rest[12])));
+        }
+
+        // Server command: a single command id after the header
+        if rest.len() >= 13 && rest[..4] == PACKET_COMMAND.to_be_bytes() {
+            self.rest = &rest[13..];
+            return Some(Ok(Incoming::Command(ServerCommand::from(rest[12]))));
+        }
+
+        // Anything after a packet that doesn't parse would be garbage too
+        self.rest = &[];
+        let ((next, _), packet) = match Packet::from_bytes((rest, 0)) {
+            Ok(parsed) => parsed,
+            Err(e) => return Some(Err(format!("Failed to parse packet: {e}"))),
+        };
+        let (seq, packet) = packet.split();
+        if let CbPacket::Unknown(packet_type) = packet {
+            return Some(Ok(Incoming::Unknown {
+                seq,
+                packet_type,
+                skipped: next.len(),
+            }));
+        }
+        self.rest = next;
+        Some(Ok(Incoming::Packet { seq, packet }))
+    }
+}
+
+// Numbers and builds packets, for EmulatedTracker as much as any other driver. Doesn't keep track of the connection
+// itself, the driver sends handshake() until something comes back and answers the server with respond().
+#[derive(Debug)]
+pub struct TrackerCore {
+    handshake: Handshake,
+    packet_number: u64,
+}
+
+impl TrackerCore {
+    pub fn new(handshake: Handshake) -> Self {
+        Self {
+            handshake,
+            packet_number: 0,
+        }
+    }
+
+    // Sequence number of the last packet built
+    pub fn packet_number(&self) -> u64 {
+        self.packet_number
+    }
+
+    // Makes the next packet built `packet_number + 1`
+    pub fn set_packet_number(&mut self, packet_number: u64) {
+        self.packet_number = packet_number;
+    }
+
+    pub fn handshake(&self) -> Result<Vec<u8>, String> {
+        self.handshake.to_bytes()
+    }
+
+    // For when what the handshake announces changes, e.g. the sensors
+    pub fn set_handshake(&mut self, handshake: Handshake) {
+        self.handshake = handshake;
+    }
+
+    // A packet that fails to serialize doesn't use up a sequence number
+    pub fn packet(&mut self, data: SbPacket) -> Result<Vec<u8>, String> {
+        let bytes = encode(self.packet_number + 1, data)?;
+        self.packet_number += 1;
+        Ok(bytes)
+    }
+
+    pub fn raw_packet(&mut self, packet: &RawPacket) -> Vec<u8> {
+        self.packet_number += 1;
+        packet.to_bytes(self.packet_number)
+    }
+
+    // An already built packet with its sequence number swapped for the next one, for resending it
+    pub fn renumber(&mut self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        if bytes.len() < HEADER_LEN {
+            return Err(format!(
+                "Packet too short to renumber ({} bytes)",
+                bytes.len()
+            ));
+        }
+        self.packet_number += 1;
+        let mut bytes = bytes.to_vec();
+        bytes[4..HEADER_LEN].copy_from_slice(&self.packet_number.to_be_bytes());
+        Ok(bytes)
+    }
+
+    pub fn heartbeat(&mut self) -> Result<Vec<u8>, String> {
+        self.packet(SbPacket::Heartbeat)
+    }
+
+    pub fn sensor_info(
+        &mut self,
+        sensor_id: u8,
+        sensor_type: ImuType,
+        sensor_status: SensorStatus,
+    ) -> Result<Vec<u8>, String> {
+        self.packet(SbPacket::SensorInfo {
+            sensor_id,
+            sensor_type,
+            sensor_status,
+        })
+    }
+
+    pub fn rotation(
+        &mut self,
+        sensor_id: u8,
+        data_type: SensorDataType,
+        rotation: SlimeQuaternion,
+        accuracy: u8,
+    ) -> Result<Vec<u8>, String> {
+        self.packet(SbPacket::RotationData {
+            sensor_id,
+            data_type,
+            quat: rotation,
+            calibration_info: accuracy,
+        })
+    }
+
+    pub fn acceleration(
+        &mut self,
+        sensor_id: u8,
+        acceleration: impl Into<Vec3>,
+    ) -> Result<Vec<u8>, String> {
+        self.packet(SbPacket::Acceleration {
+            sensor_id,
+            vector: acceleration.into().into(),
+        })
+    }
+
+    // What a tracker sends back when the server asks for it: heartbeats get a heartbeat and pings get echoed
+    pub fn respond(&mut self, incoming: &Incoming) -> Result<Option<Vec<u8>>, String> {
+        match incoming {
+            Incoming::Packet {
+                packet: CbPacket::Heartbeat,
+                ..
+            } => self.heartbeat().map(Some),
+            Incoming::Packet {
+                packet: CbPacket::Ping { challenge },
+                ..
+            } => self
+                .packet(SbPacket::Ping {
+                    challenge: *challenge,
+                })
+                .map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_core() {
+        let mut core = TrackerCore::new(Handshake {
+            board: BoardType::Unknown(0),
+            imu: ImuType::Bno085,
+            mcu: McuType::Unknown(0),
+            imu_info: (0, 0, 0),
+            build: 13,
+            firmware: "core".to_string(),
+            mac_address: [1, 2, 3, 4, 5, 6],
+            legacy: false,
+        });
+        assert_eq!(core.handshake().unwrap()[4..12], 0u64.to_be_bytes());
+        let sensor_info = core
+            .sensor_info(0, ImuType::Bno085, SensorStatus::Ok)
+            .unwrap();
+        assert_eq!(sensor_info[4..12], 1u64.to_be_bytes());
+        core.acceleration(0, Vec3::ZERO).unwrap();
+        assert_eq!(core.packet_number(), 2);
+        let resent = core.renumber(&sensor_info).unwrap();
+        assert_eq!(resent[4..12], 3u64.to_be_bytes());
+        assert_eq!(resent[12..], sensor_info[12..]);
+        assert!(core.renumber(&[0; 11]).is_err());
+        assert_eq!(core.packet_number(), 3);
+
+        // Sensor ack, heartbeat and a command in one datagram
+        let mut datagram = PACKET_SENSOR_INFO.to_be_bytes().to_vec();
+        datagram.extend_from_slice(&[0; 8]);
+        datagram.extend_from_slice(&[0, 1]);
+        datagram.extend_from_slice(&1u32.to_be_bytes());
+        datagram.extend_from_slice(&7u64.to_be_bytes());
+        datagram.extend_from_slice(&PACKET_COMMAND.to_be_bytes());
+        datagram.extend_from_slice(&[0; 8]);
+        datagram.push(3);
+        let incoming: Vec<_> = decode(&datagram).map(Result::unwrap).collect();
+        assert!(matches!(incoming[0], Incoming::SensorAck(0)));
+        assert!(matches!(
+            incoming[1],
+            Incoming::Packet {
+                seq: 7,
+                packet: CbPacket::Heartbeat
+            }
+        ));
+        assert!(matches!(
+            incoming[2],
+            Incoming::Command(ServerCommand::Blink)
+        ));
+
+        let heartbeat = core.respond(&incoming[1]).unwrap().unwrap();
+        assert_eq!(heartbeat[4..12], 4u64.to_be_bytes());
+        assert!(core.respond(&incoming[0]).unwrap().is_none());
+    }
+}
@@ -0,0 +1,179 @@
+// Background tasks that send made up telemetry, for testing how a server handles it.
+// They all stop on their own once the tracker is deinitialized or loses the server.
+
+use crate::{EmulatedTracker, TrackerError};
+use firmware_protocol::SbPacket;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Instant};
+
+const BATTERY_INTERVAL: Duration = Duration::from_secs(1);
+const MAG_ACCURACY_INTERVAL: Duration = Duration::from_millis(500);
+const SIGNAL_STRENGTH_INTERVAL: Duration = Duration::from_secs(2);
+// WiFi is per tracker, firmware reports it with this sensor id instead of a real one
+const TRACKER_SENSOR_ID: u8 = 255;
+
+// Rough single-cell LiPo discharge curve, 3.3V when empty up to 4.2V when full
+pub fn lipo_voltage(percentage: f32) -> f32 {
+    let level = (percentage / 100.0).clamp(0.0, 1.0);
+    3.3 + 0.9 * level.powf(0.7)
+}
+
+// Random walk step for fluctuating values, moves by up to 5% of the range and never leaves it
+fn drift(value: f32, min: f32, max: f32) -> f32 {
+    let step = (max - min) * 0.05;
+    if step <= 0.0 {
+        return min;
+    }
+    (value + rand::random_range(-step..=step)).clamp(min, max)
+}
+
+impl EmulatedTracker {
+    // Sends a battery level dropping by `drain_per_min` percent every minute, starting from `start_pct`.
+    // `voltage_curve` maps the percentage (0-100) to the voltage to report, lipo_voltage works for most boards.
+    pub fn start_battery_simulation(
+        &self,
+        start_pct: f32,
+        drain_per_min: f32,
+        voltage_curve: impl Fn(f32) -> f32 + Send + 'static,
+    ) -> Result<JoinHandle<()>, String> {
+        let socket = self
+            .socket
+            .as_ref()
+            .ok_or(TrackerError::NotInitialized)?
+            .clone();
+        let status_rx = self.status_rx.clone();
+        let send_path = self.send_path.clone();
+
+        Ok(self.spawn_task("battery_simulation", async move {
+            let start = Instant::now();
+            let mut ticker = interval(BATTERY_INTERVAL);
+            loop {
+                ticker.tick().await;
+                if status_rx.borrow().is_stopped() {
+                    break;
+                }
+
+                let minutes = start.elapsed().as_secs_f32() / 60.0;
+                let percentage = (start_pct - drain_per_min * minutes).clamp(0.0, 100.0);
+                // Same checks as send_battery_level, a curve going past MAX_BATTERY_VOLTAGE is the caller's bug
+                let result =
+                    match EmulatedTracker::battery_packet(percentage, voltage_curve(percentage)) {
+                        Ok(data) => send_path.send_recorded(&socket, data).await,
+                        Err(e) => Err(e.into()),
+                    };
+                if let Err(e) = result {
+                    warn!(error = %e, "Failed to send simulated battery level");
+                }
+
+                // Nothing left to drain
+                if percentage <= 0.0 {
+                    break;
+                }
+            }
+        }))
+    }
+
+    // Sends a magnetometer accuracy for each of `sensor_ids` that wanders around between `min` and `max`, every
+    // sensor drifting on its own like real IMUs do
+    pub fn start_mag_accuracy_simulation(
+        &self,
+        sensor_ids: impl IntoIterator<Item = u8>,
+        min: f32,
+        max: f32,
+    ) -> Result<JoinHandle<()>, String> {
+        let socket = self
+            .socket
+            .as_ref()
+            .ok_or(TrackerError::NotInitialized)?
+            .clone();
+        let status_rx = self.status_rx.clone();
+        let send_path = self.send_path.clone();
+        let (min, max) = (min.min(max), min.max(max));
+        let mut accuracies: Vec<(u8, f32)> = sensor_ids
+            .into_iter()
+            .map(|sensor_id| (sensor_id, (min + max) / 2.0))
+            .collect();
+
+        Ok(self.spawn_task("mag_accuracy_simulation", async move {
+            let mut ticker = interval(MAG_ACCURACY_INTERVAL);
+            loop {
+                ticker.tick().await;
+                if status_rx.borrow().is_stopped() {
+                    break;
+                }
+
+                for (sensor_id, accuracy) in accuracies.iter_mut() {
+                    *accuracy = drift(*accuracy, min, max);
+                    let data = SbPacket::MagAccuracy {
+                        sensor_id: *sensor_id,
+                        accuracy: *accuracy,
+                    };
+                    if let Err(e) = send_path.send_recorded(&socket, data).await {
+                        warn!(sensor_id = *sensor_id, error = %e, "Failed to send simulated magnetometer accuracy");
+                    }
+                }
+            }
+        }))
+    }
+
+    // Sends a WiFi signal strength (RSSI, in dBm) that wanders around between `min_dbm` and `max_dbm`, e.g. -80 and
+    // -40 for a tracker moving around a room
+    pub fn start_signal_strength_simulation(
+        &self,
+        min_dbm: i8,
+        max_dbm: i8,
+    ) -> Result<JoinHandle<()>, String> {
+        let socket = self
+            .socket
+            .as_ref()
+            .ok_or(TrackerError::NotInitialized)?
+            .clone();
+        let status_rx = self.status_rx.clone();
+        let send_path = self.send_path.clone();
+        let (min, max) = (min_dbm.min(max_dbm) as f32, min_dbm.max(max_dbm) as f32);
+        let mut strength = (min + max) / 2.0;
+
+        Ok(self.spawn_task("signal_strength_simulation", async move {
+            let mut ticker = interval(SIGNAL_STRENGTH_INTERVAL);
+            loop {
+                ticker.tick().await;
+                if status_rx.borrow().is_stopped() {
+                    break;
+                }
+
+                strength = drift(strength, min, max);
+                let data = SbPacket::SignalStrength {
+                    sensor_id: TRACKER_SENSOR_ID,
+                    strength: strength.round() as i8,
+                };
+                if let Err(e) = send_path.send_recorded(&socket, data).await {
+                    warn!(error = %e, "Failed to send simulated signal strength");
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lipo_voltage() {
+        assert!((lipo_voltage(0.0) - 3.3).abs() < 1e-5);
+        assert!((lipo_voltage(100.0) - 4.2).abs() < 1e-5);
+        assert!(lipo_voltage(25.0) < lipo_voltage(75.0));
+        assert_eq!(lipo_voltage(150.0), lipo_voltage(100.0));
+    }
+
+    #[test]
+    fn test_drift_stays_in_range() {
+        let mut value = 2.0;
+        for _ in 0..1000 {
+            value = drift(value, 1.0, 3.0);
+            assert!((1.0..=3.0).contains(&value));
+        }
+        assert_eq!(drift(5.0, 2.0, 2.0), 2.0);
+    }
+}